@@ -1,17 +1,20 @@
 use std::{
-    collections::{HashMap, HashSet},
     fs::File,
     io::{BufReader, Seek, SeekFrom},
     net::ToSocketAddrs,
     path::Path,
-    sync::Mutex,
+    sync::{Mutex, OnceLock},
 };
 
 use binrw::{io::Cursor, BinRead, BinReaderExt, BinResult, FilePtr64};
 
-use crate::filesystem::HashToIndex;
-use crate::hash_labels::HashLabels;
-use crate::{CompressedFileSystem, FileNode, FileSystem, Hash40};
+#[cfg(feature = "dir-listing")]
+use crate::dir_listing::DirListing;
+use crate::hash_labels::{HashLabels, GLOBAL_LABELS};
+use crate::{
+    ArcLookup, CompressedFileSystem, DependencyGraph, FileDataIdx, FileNode, FileSystem, Hash40, LookupError, Region,
+    ReverseIndex,
+};
 
 pub trait SeekRead: std::io::Read + std::io::Seek {}
 impl<R: std::io::Read + std::io::Seek> SeekRead for R {}
@@ -32,123 +35,35 @@ pub struct ArcFile {
     #[br(calc = Mutex::new(Box::new(Cursor::new([])) as _))]
     pub reader: Mutex<Box<dyn SeekRead + Send>>,
 
-    #[cfg(feature = "dir-listing")]
-    #[br(calc = generate_dir_listing(&file_system))]
-    pub dirs: HashMap<Hash40, Vec<FileNode>>,
-}
-
-#[cfg(feature = "dir-listing")]
-fn parents_of_dir(dir: Hash40, labels: &mut HashLabels) -> Option<Vec<(Hash40, FileNode)>> {
-    let label = dir.label(&labels)?.to_owned();
-    let mut label = &label[..];
-    let mut hashes = Vec::new();
-    let mut last_hash = dir;
-
-    while let Some(len) = label.trim_end_matches('/').rfind('/') {
-        label = &label[..len];
-
-        let hash = labels.add_label(label);
-        hashes.push((hash, FileNode::Dir(last_hash)));
-        last_hash = hash;
-    }
-
-    hashes.push((crate::hash40::hash40("/"), FileNode::Dir(last_hash)));
-
-    Some(hashes)
-}
-
-#[cfg(feature = "dir-listing")]
-fn dir_listing_flat<'a>(
-    fs: &'a FileSystem,
-    labels: &'a mut HashLabels,
-) -> impl Iterator<Item = (Hash40, FileNode)> + 'a {
-    let dirs: HashSet<_> = fs
-        .file_paths
-        .iter()
-        .map(|path| path.parent.hash40())
-        .collect();
-
-    let mut stream_dirs = Vec::new();
-    for path_hash in fs.stream_hash_to_entries.iter().map(HashToIndex::hash40) {
-        if let Some(label) = path_hash
-            .label(&labels)
-            .and_then(|label| label.rfind('/').map(|pos| label[..pos].to_owned()))
-        {
-            let mut label = &label[..];
-            let mut last_hash = crate::hash40::hash40(label);
-            labels.add_label(label);
-
-            while let Some(len) = label.trim_end_matches('/').rfind('/') {
-                label = &label[..len];
-
-                let hash = labels.add_label(label);
-                stream_dirs.push((hash, FileNode::Dir(last_hash)));
-                last_hash = hash;
-            }
-
-            stream_dirs.push((crate::hash40::hash40("/"), FileNode::Dir(last_hash)));
-
-            labels.add_label(label);
-        }
-    }
-
-    let stream_paths: Vec<(Hash40, &str)> = fs
-        .stream_hash_to_entries
-        .iter()
-        .filter_map(|entry| {
-            entry
-                .hash40()
-                .label(labels)
-                .map(|path| (entry.hash40(), path))
-        })
-        .collect();
-    let stream_files: Vec<(Hash40, FileNode)> = stream_paths
-        .iter()
-        .flat_map(|(path_hash, path)| {
-            path.rfind('/').map(|pos| {
-                let dir = crate::hash40::hash40(&path[..pos]);
-
-                (dir, FileNode::File(*path_hash))
-            })
-        })
-        .collect();
-
-    // Generate parents for directories
-    let dirs = dirs
-        .into_iter()
-        .filter_map(move |dir| parents_of_dir(dir, labels).map(|x| x.into_iter()))
-        .flatten();
-
-    // Generate parents for files
-    fs.file_paths
-        .iter()
-        .map(|path| (path.parent.hash40(), FileNode::File(path.path.hash40())))
-        .chain(dirs)
-        .chain(stream_files.into_iter())
-        .chain(stream_dirs.into_iter())
-}
+    /// The labels used for directory listing and [`label`](ArcFile::label) lookups
+    #[br(calc = HashLabels::new())]
+    pub labels: HashLabels,
 
-#[cfg(feature = "dir-listing")]
-fn generate_dir_listing(fs: &FileSystem) -> HashMap<Hash40, Vec<FileNode>> {
-    let mut dirs = HashMap::new();
-
-    let mut labels = crate::hash_labels::GLOBAL_LABELS.write();
-    for (parent, child) in dir_listing_flat(fs, &mut labels) {
-        let listing = dirs.entry(parent).or_insert_with(Vec::new);
-        match listing.binary_search(&child) {
-            Ok(_) => (),
-            Err(insert_point) => listing.insert(insert_point, child),
-        }
-    }
+    /// Generated on first use, as it requires going through every file in the arc
+    #[cfg(feature = "dir-listing")]
+    #[br(calc = OnceLock::new())]
+    dirs: OnceLock<DirListing>,
 
-    dirs
+    /// Generated on first use, and cleared whenever the file infos or file info to datas are
+    /// borrowed mutably through [`ArcLookup`]
+    #[br(calc = OnceLock::new())]
+    pub(crate) reverse_index: OnceLock<ReverseIndex>,
 }
 
 impl ArcFile {
+    /// Open an arc which shares the [global labels](crate::GLOBAL_LABELS), which must be set
+    /// beforehand for directory listing to work. Setting the global labels again afterwards
+    /// doesn't affect arcs which are already open. Use [`open_with_labels`](ArcFile::open_with_labels)
+    /// to avoid depending on global state.
     pub fn open<P: AsRef<Path>>(path: P) -> BinResult<Self> {
         Self::from_reader(BufReader::new(File::open(path)?))
     }
 
+    /// Open an arc which uses `labels` for directory listing and label lookups
+    pub fn open_with_labels<P: AsRef<Path>>(path: P, labels: HashLabels) -> BinResult<Self> {
+        Self::from_reader_with_labels(BufReader::new(File::open(path)?), labels)
+    }
+
     #[cfg(feature = "network")]
     pub fn open_over_network<Addr: ToSocketAddrs>(ip: Addr) -> BinResult<Self> {
         let mut reader = BufReader::new(network_reader::NetworkReader::new(ip)?);
@@ -158,69 +73,207 @@ impl ArcFile {
         Self::from_reader(reader)
     }
 
-    pub fn from_reader<R: SeekRead + Send + 'static>(mut reader: R) -> BinResult<Self> {
-        let arc: Self = reader.read_le()?;
+    /// Equivalent to [`open`](ArcFile::open) for an arbitrary reader.
+    pub fn from_reader<R: SeekRead + Send + 'static>(reader: R) -> BinResult<Self> {
+        let labels = GLOBAL_LABELS.read().clone();
+
+        Self::from_reader_with_labels(reader, labels)
+    }
+
+    /// Equivalent to [`open_with_labels`](ArcFile::open_with_labels) for an arbitrary reader.
+    pub fn from_reader_with_labels<R: SeekRead + Send + 'static>(mut reader: R, labels: HashLabels) -> BinResult<Self> {
+        let mut arc: Self = reader.read_le()?;
 
         *arc.reader.lock().unwrap() = Box::new(reader);
+        arc.labels = labels;
+
+        Ok(arc)
+    }
+
+    /// Open the arc and [`validate`](ArcLookup::validate) its tables, so that lookups into
+    /// a malformed arc return errors rather than panicking. An arc which parses but fails
+    /// validation is reported as [`Error::Validation`](crate::Error::Validation).
+    pub fn open_validated<P: AsRef<Path>>(path: P) -> Result<Self, crate::Error> {
+        Self::from_reader_validated(BufReader::new(File::open(path)?))
+    }
+
+    /// Equivalent to [`open_validated`](ArcFile::open_validated) for an arbitrary reader.
+    pub fn from_reader_validated<R: SeekRead + Send + 'static>(reader: R) -> Result<Self, crate::Error> {
+        let arc = Self::from_reader(reader)?;
+        arc.validate()?;
 
         Ok(arc)
     }
 
+    /// Get the label of a hash from the labels the arc was opened with. With `dir-listing`
+    /// enabled, directories which only have a label by being the parent of a labelled path are
+    /// also labelled, which requires generating the directory listing.
+    pub fn label<Hash: Into<Hash40>>(&self, hash: Hash) -> Option<&str> {
+        let hash = hash.into();
+        let label = hash.label(&self.labels);
+
+        #[cfg(feature = "dir-listing")]
+        let label = label.or_else(|| self.dir_listing().parent_label(hash));
+
+        label
+    }
+
+    /// Get the children of a directory. The listing of every directory is generated the first
+    /// time this is called.
     #[cfg(feature = "dir-listing")]
     pub fn get_dir_listing<Hash: Into<Hash40>>(&self, hash: Hash) -> Option<&[FileNode]> {
-        self.dirs.get(&hash.into()).map(AsRef::as_ref)
+        self.dir_listing().get(hash.into())
+    }
+
+    /// Get the children of every directory, keyed by the hash of the directory. The listing is
+    /// generated the first time this or [`get_dir_listing`](ArcFile::get_dir_listing) is called.
+    #[cfg(feature = "dir-listing")]
+    pub fn dirs(&self) -> &std::collections::HashMap<Hash40, Vec<FileNode>> {
+        self.dir_listing().dirs()
+    }
+
+    /// Get the index of which paths reference each file data, generating it the first time this
+    /// is called. Modifying `file_system` directly won't update an index which has already been
+    /// generated.
+    pub fn reverse_index(&self) -> &ReverseIndex {
+        self.reverse_index.get_or_init(|| ReverseIndex::generate(self))
+    }
+
+    /// Get the paths of every file which uses a file data in the given region
+    pub fn get_file_data_paths(&self, file_data_index: FileDataIdx, region: Region) -> Vec<Hash40> {
+        self.reverse_index().paths(file_data_index, region)
+    }
+
+    /// Build a graph of which files share data and which directories redirect where in the given
+    /// region, see [`DependencyGraph`]
+    pub fn dependency_graph(&self, region: Region) -> Result<DependencyGraph, LookupError> {
+        DependencyGraph::generate(self, self.reverse_index(), region)
+    }
+
+    #[cfg(feature = "dir-listing")]
+    fn dir_listing(&self) -> &DirListing {
+        self.dirs.get_or_init(|| DirListing::generate(&self.file_system, &self.labels))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_arc::ArcBuilder;
+    use crate::{TableKind, ValidationError};
+    use std::sync::Arc;
+
+    fn tree_labels(arc: &ArcFile, hash: Hash40) -> Vec<String> {
+        arc.get_dir_listing(hash)
+            .unwrap()
+            .iter()
+            .map(|file| match file {
+                FileNode::File(file) => arc.label(*file).map_or_else(|| format!("{:#x}", file.as_u64()), str::to_owned),
+                FileNode::Dir(dir) => format!("{}/", arc.label(*dir).map_or_else(|| format!("{:#x}", dir.as_u64()), str::to_owned)),
+            })
+            .collect()
+    }
 
-    fn print_tree_hash(arc: &ArcFile, hash: Hash40, depth: usize) {
-        for file in arc.get_dir_listing(hash).unwrap() {
-            (0..depth).for_each(|_| print!("    "));
-            match file {
-                FileNode::File(file) => {
-                    println!(
-                        "L {}",
-                        file.global_label()
-                            .unwrap_or_else(|| format!("{:#x}", file.as_u64()))
-                    );
-                }
-                FileNode::Dir(dir) => {
-                    println!(
-                        "L {}",
-                        dir.global_label()
-                            .unwrap_or_else(|| format!("{:#x}", dir.as_u64()))
-                    );
-                    //print_tree_hash(arc, *dir, depth + 1);
-                }
-            }
-        }
-    }
-
-    fn print_tree(arc: &ArcFile, dir: &str) {
-        println!("{}:", dir);
-        print_tree_hash(arc, dir.into(), 1);
+    fn sorted_tree(arc: &ArcFile, dir: &str) -> Vec<String> {
+        let mut labels = tree_labels(arc, dir.into());
+        labels.sort();
+        labels
     }
 
     #[test]
     fn test_listing() {
-        Hash40::set_global_labels_file("/home/jam/Downloads/hashes.txt");
-        let arc = ArcFile::open("/home/jam/re/ult/900/data.arc").unwrap();
-        //let arc = ArcFile::open_over_network(("192.168.86.32", 43022)).unwrap();
-
-        print_tree(&arc, "/");
-        //dbg!(arc.get_dir_listing("fighter/mario/model/body/c00/"));
+        let arc = ArcBuilder::sample().open();
+
+        assert_eq!(sorted_tree(&arc, "/"), ["fighter/", "stream:/", "ui/"]);
+        assert_eq!(arc.dirs()[&Hash40::from("/")].len(), 3);
+        assert_eq!(sorted_tree(&arc, "fighter/mario/model/body/c00"), [
+            "fighter/mario/model/body/c00/def_mario_001_col.nutexb",
+            "fighter/mario/model/body/c00/leyes_eye_mario_l_col.nutexb",
+            "fighter/mario/model/body/c00/model.numdlb",
+        ]);
     }
 
     #[test]
     fn test_stream_listing() {
-        Hash40::set_global_labels_file("/home/jam/Downloads/hashes.txt");
-        let arc = ArcFile::open("/home/jam/re/ult/900/data.arc").unwrap();
-        //let arc = ArcFile::open_over_network(("192.168.86.32", 43022)).unwrap();
+        let arc = ArcBuilder::sample().open();
+
+        assert_eq!(sorted_tree(&arc, "stream:"), ["stream:/movie/", "stream:/sound/"]);
+        assert_eq!(sorted_tree(&arc, "stream:/sound/bgm"), [
+            "stream:/sound/bgm/bgm_crs2_01_menu.nus3audio",
+            "stream:/sound/bgm/bgm_crs2_01_menu.nus3bank",
+        ]);
+    }
+
+    #[test]
+    fn lazy_listing() {
+        let labels = HashLabels::from_string("fighter/mario/model/body/c00/model.numdlb\nfighter/mario/model/body/c00");
+        let arc = ArcFile::from_reader_with_labels(std::io::Cursor::new(ArcBuilder::sample().build()), labels).unwrap();
+        assert!(arc.dirs.get().is_none());
+
+        assert_eq!(arc.label("fighter/mario/model/body/c00"), Some("fighter/mario/model/body/c00"));
+        assert!(arc.dirs.get().is_none());
 
-        print_tree(&arc, "stream:");
-        //dbg!(arc.get_dir_listing("fighter/mario/model/body/c00/"));
+        // Parents of labelled directories get labels of their own once the listing is generated
+        assert_eq!(arc.label("fighter/mario/model"), Some("fighter/mario/model"));
+        assert!(arc.dirs.get().is_some());
+
+        assert_eq!(sorted_tree(&arc, "fighter"), ["fighter/mario/"]);
+        assert_eq!(sorted_tree(&arc, "fighter/mario/model/body/c00")[2], "fighter/mario/model/body/c00/model.numdlb");
+    }
+
+    #[test]
+    fn separate_labels() {
+        let mario = ArcBuilder::new().file("fighter/mario/param/vl.prc", b"mario").open();
+        let luigi = ArcBuilder::new().file("fighter/luigi/param/vl.prc", b"luigi").open();
+        let unlabelled = ArcFile::from_reader_with_labels(
+            std::io::Cursor::new(ArcBuilder::sample().build()),
+            HashLabels::new(),
+        ).unwrap();
+
+        assert_eq!(sorted_tree(&mario, "fighter"), ["fighter/mario/"]);
+        assert_eq!(sorted_tree(&luigi, "fighter"), ["fighter/luigi/"]);
+        assert_eq!(mario.label("fighter/mario/param/vl.prc"), Some("fighter/mario/param/vl.prc"));
+        assert_eq!(mario.label("fighter/luigi/param/vl.prc"), None);
+
+        // Without labels the listing can't be connected back to the root
+        assert!(unlabelled.get_dir_listing("/").is_none());
+        assert!(unlabelled.label("fighter/mario/model/body/c00/model.numdlb").is_none());
+
+        // Generating the listing doesn't leak labels into the global ones
+        assert!(Hash40::from("fighter/mario").global_label().is_none());
+
+        // Arcs opened with the global labels share them instead of each holding a copy
+        let first = ArcFile::from_reader(std::io::Cursor::new(ArcBuilder::sample().build())).unwrap();
+        let second = ArcFile::from_reader(std::io::Cursor::new(ArcBuilder::sample().build())).unwrap();
+        assert!(Arc::ptr_eq(&first.labels.labels, &second.labels.labels));
+    }
+
+    #[test]
+    fn open_validated() {
+        let data = ArcBuilder::sample().build();
+        ArcFile::from_reader_validated(std::io::Cursor::new(data)).unwrap();
+
+        // Point the first file path past the end of the file info indices
+        let fs = ArcBuilder::sample().open().file_system;
+        let file_paths = 0x110
+            + fs.quick_dirs.len() * 12
+            + fs.stream_hash_to_entries.len() * (8 + 12)
+            + fs.stream_file_indices.len() * 4
+            + fs.stream_datas.len() * 16
+            + 8
+            + fs.file_info_buckets.len() * 8
+            + fs.file_hash_to_path_index.len() * 8;
+        let data = ArcBuilder::sample().patch_table(file_paths + 5, &[0xFF; 3]).build();
+
+        assert!(matches!(
+            ArcFile::from_reader_validated(std::io::Cursor::new(data)),
+            Err(crate::Error::Validation(ValidationError::IndexOutOfBounds {
+                table: TableKind::FilePaths,
+                index: 0,
+                target: TableKind::FileInfoIndices,
+                target_index: 0xFF_FFFF,
+                ..
+            }))
+        ));
     }
 }
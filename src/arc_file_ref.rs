@@ -0,0 +1,308 @@
+use std::{
+    borrow::Cow,
+    mem::{align_of, size_of},
+    slice,
+};
+
+use binrw::{io::Cursor, BinRead, BinReaderExt, BinResult};
+
+use crate::filesystem::*;
+use crate::{CompressedFileSystem, Hash40, LookupError, Region, ArcLookup};
+
+/// The header at the start of every data.arc, without following the file system pointer
+#[derive(BinRead)]
+#[br(magic = 0xABCD_EF98_7654_3210_u64)]
+struct ArcHeader {
+    stream_section_offset: u64,
+    file_section_offset: u64,
+    shared_section_offset: u64,
+    file_system_offset: u64,
+    patch_section: u64,
+}
+
+/// A borrowed version of [`ArcFile`](crate::ArcFile) over an in-memory data.arc.
+///
+/// If the file system section is stored uncompressed (such as a dump of the table as the game
+/// loads it) the tables are borrowed directly from the buffer wherever their layout allows it,
+/// otherwise they are decompressed into owned storage. File contents are always read straight
+/// from the buffer.
+pub struct ArcFileRef<'a> {
+    pub stream_section_offset: u64,
+    pub file_section_offset: u64,
+    pub shared_section_offset: u64,
+    pub patch_section: u64,
+
+    pub fs_header: FileSystemHeader,
+    pub stream_header: StreamHeader,
+
+    pub quick_dirs: Cow<'a, [QuickDir]>,
+    pub stream_hash_to_entries: Cow<'a, [HashToIndex]>,
+    pub stream_entries: Cow<'a, [StreamEntry]>,
+    pub stream_file_indices: Cow<'a, [u32]>,
+    pub stream_datas: Cow<'a, [StreamData]>,
+
+    pub file_info_buckets: Cow<'a, [FileInfoBucket]>,
+    pub file_hash_to_path_index: Cow<'a, [HashToIndex]>,
+    pub file_paths: Cow<'a, [FilePath]>,
+    pub file_info_indices: Cow<'a, [FileInfoIndex]>,
+    pub dir_hash_to_info_index: Cow<'a, [HashToIndex]>,
+    pub dir_infos: Cow<'a, [DirInfo]>,
+    pub folder_offsets: Cow<'a, [DirectoryOffset]>,
+    pub folder_child_hashes: Cow<'a, [HashToIndex]>,
+    pub file_infos: Cow<'a, [FileInfo]>,
+    pub file_info_to_datas: Cow<'a, [FileInfoToFileData]>,
+    pub file_datas: Cow<'a, [FileData]>,
+
+    pub(crate) data: &'a [u8],
+}
+
+impl<'a> ArcFileRef<'a> {
+    /// Parse an arc from a buffer holding the entire data.arc
+    ///
+    /// Only an uncompressed file system section can have its tables borrowed from `data`. The
+    /// data.arc shipped with the game always compresses it, so for those the tables are
+    /// decompressed into owned storage and only file contents are borrowed.
+    pub fn new(data: &'a [u8]) -> BinResult<Self> {
+        let header: ArcHeader = Cursor::new(data).read_le()?;
+
+        let fs_data = data
+            .get(header.file_system_offset as usize..)
+            .ok_or_else(|| out_of_bounds(header.file_system_offset))?;
+
+        let is_compressed = fs_data.get(..4) == Some(&0x10_u32.to_le_bytes()[..]);
+
+        let mut arc = if is_compressed {
+            let fs = Cursor::new(fs_data).read_le::<CompressedFileSystem>()?.0;
+
+            Self::from_owned(data, fs)
+        } else {
+            Self::from_borrowed(data, fs_data)?
+        };
+
+        arc.stream_section_offset = header.stream_section_offset;
+        arc.file_section_offset = header.file_section_offset;
+        arc.shared_section_offset = header.shared_section_offset;
+        arc.patch_section = header.patch_section;
+
+        Ok(arc)
+    }
+
+    fn from_owned(data: &'a [u8], fs: FileSystem) -> Self {
+        Self {
+            stream_section_offset: 0,
+            file_section_offset: 0,
+            shared_section_offset: 0,
+            patch_section: 0,
+            fs_header: fs.fs_header,
+            stream_header: fs.stream_header,
+            quick_dirs: fs.quick_dirs.into(),
+            stream_hash_to_entries: fs.stream_hash_to_entries.into(),
+            stream_entries: fs.stream_entries.into(),
+            stream_file_indices: fs.stream_file_indices.into(),
+            stream_datas: fs.stream_datas.into(),
+            file_info_buckets: fs.file_info_buckets.into(),
+            file_hash_to_path_index: fs.file_hash_to_path_index.into(),
+            file_paths: fs.file_paths.into(),
+            file_info_indices: fs.file_info_indices.into(),
+            dir_hash_to_info_index: fs.dir_hash_to_info_index.into(),
+            dir_infos: fs.dir_infos.into(),
+            folder_offsets: fs.folder_offsets.into(),
+            folder_child_hashes: fs.folder_child_hashes.into(),
+            file_infos: fs.file_infos.into(),
+            file_info_to_datas: fs.file_info_to_datas.into(),
+            file_datas: fs.file_datas.into(),
+            data,
+        }
+    }
+
+    fn from_borrowed(data: &'a [u8], fs_data: &'a [u8]) -> BinResult<Self> {
+        let mut tables = TableReader { bytes: fs_data, pos: 0 };
+
+        let fs_header: FileSystemHeader = tables.read()?;
+        tables.pos = 0x100;
+        let stream_header: StreamHeader = tables.read()?;
+
+        let quick_dirs = tables.table(stream_header.quick_dir_count)?;
+        let stream_hash_to_entries = tables.table(stream_header.stream_hash_count)?;
+        let stream_entries = tables.table(stream_header.stream_hash_count)?;
+        let stream_file_indices = tables.table(stream_header.stream_file_index_count)?;
+        let stream_datas = tables.table(stream_header.stream_offset_entry_count)?;
+
+        let hash_index_group_count: u32 = tables.read()?;
+        let bucket_count: u32 = tables.read()?;
+
+        let fs = fs_header;
+        Ok(Self {
+            stream_section_offset: 0,
+            file_section_offset: 0,
+            shared_section_offset: 0,
+            patch_section: 0,
+            quick_dirs,
+            stream_hash_to_entries,
+            stream_entries,
+            stream_file_indices,
+            stream_datas,
+            file_info_buckets: tables.table(bucket_count)?,
+            file_hash_to_path_index: tables.table(hash_index_group_count)?,
+            file_paths: tables.table(fs.file_info_path_count)?,
+            file_info_indices: tables.table(fs.file_info_index_count)?,
+            dir_hash_to_info_index: tables.table(fs.folder_count)?,
+            dir_infos: tables.table(fs.folder_count)?,
+            folder_offsets: tables.table(
+                fs.folder_offset_count_1 as u64 + fs.folder_offset_count_2 as u64 + fs.extra_folder as u64,
+            )?,
+            folder_child_hashes: tables.table(fs.hash_folder_count)?,
+            file_infos: tables.table(
+                fs.file_info_count as u64 + fs.file_data_count_2 as u64 + fs.extra_count as u64,
+            )?,
+            file_info_to_datas: tables.table(
+                fs.file_info_sub_index_count as u64 + fs.file_data_count_2 as u64 + fs.extra_count_2 as u64,
+            )?,
+            file_datas: tables.table(
+                fs.file_data_count as u64 + fs.file_data_count_2 as u64 + fs.extra_count as u64,
+            )?,
+            fs_header,
+            stream_header,
+            data,
+        })
+    }
+
+    /// Get the contents of a file, borrowing from the underlying buffer if the file is stored
+    /// uncompressed
+    pub fn get_file_bytes<Hash: Into<Hash40>>(&self, hash: Hash, region: Region) -> Result<Cow<'a, [u8]>, LookupError> {
+        let metadata = self.get_file_metadata(hash, region)?;
+
+        let raw = self.data
+            .get(metadata.offset as usize..)
+            .and_then(|data| data.get(..metadata.comp_size as usize))
+            .ok_or_else(|| LookupError::FileRead(std::io::ErrorKind::UnexpectedEof.into()))?;
+
+        if metadata.is_compressed {
+            if !metadata.uses_zstd {
+                return Err(LookupError::UnsupportedCompression)
+            }
+
+            let mut data = Vec::new();
+            crate::zstd_backend::copy_decode_bounded(raw, &mut data, metadata.decomp_size)?;
+
+            Ok(Cow::Owned(data))
+        } else {
+            Ok(Cow::Borrowed(raw))
+        }
+    }
+}
+
+fn out_of_bounds(pos: u64) -> binrw::Error {
+    binrw::Error::Io(std::io::Error::new(
+        std::io::ErrorKind::UnexpectedEof,
+        format!("offset {:#x} is out of bounds of the arc", pos),
+    ))
+}
+
+struct TableReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> TableReader<'a> {
+    fn take(&mut self, len: usize) -> BinResult<&'a [u8]> {
+        let bytes = self.pos
+            .checked_add(len)
+            .and_then(|end| self.bytes.get(self.pos..end))
+            .ok_or_else(|| out_of_bounds(self.pos as u64))?;
+
+        self.pos += len;
+
+        Ok(bytes)
+    }
+
+    fn read<T: BinRead<Args = ()>>(&mut self) -> BinResult<T> {
+        let mut cursor = Cursor::new(&self.bytes[self.pos.min(self.bytes.len())..]);
+        let value = cursor.read_le()?;
+        self.pos += cursor.position() as usize;
+
+        Ok(value)
+    }
+
+    /// Borrow a table from the buffer if the in-memory layout matches, otherwise parse a copy
+    fn table<T: TableEntry, Count: Into<u64>>(&mut self, count: Count) -> BinResult<Cow<'a, [T]>> {
+        let count = count.into() as usize;
+        let len = count
+            .checked_mul(T::SIZE)
+            .ok_or_else(|| out_of_bounds(self.pos as u64))?;
+        let bytes = self.take(len)?;
+
+        let is_castable = cfg!(target_endian = "little")
+            && size_of::<T>() == T::SIZE
+            && bytes.as_ptr().align_offset(align_of::<T>()) == 0;
+
+        if is_castable {
+            // Safety: the size, alignment and endianness have been checked above and
+            // `TableEntry` guarantees any bit pattern is a valid `T`
+            Ok(Cow::Borrowed(unsafe { slice::from_raw_parts(bytes.as_ptr() as *const T, count) }))
+        } else {
+            let mut cursor = Cursor::new(bytes);
+
+            (0..count)
+                .map(|_| cursor.read_le())
+                .collect::<BinResult<Vec<T>>>()
+                .map(Cow::Owned)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_arc::ArcBuilder;
+    use std::convert::TryInto;
+
+    #[test]
+    fn file_bytes() {
+        for data in &[ArcBuilder::sample().build(), ArcBuilder::sample().uncompressed_table().build()] {
+            let arc = ArcFileRef::new(data).unwrap();
+
+            let file = arc.get_file_bytes("fighter/mario/model/body/c00/model.numdlb", Region::UsEnglish).unwrap();
+            assert!(matches!(file, Cow::Borrowed(b"mario model")));
+
+            let file = arc.get_file_bytes("fighter/mario/model/body/c00/def_mario_001_col.nutexb", Region::UsEnglish).unwrap();
+            assert!(matches!(file, Cow::Owned(_)));
+            assert_eq!(file, &[0x55; 0x300][..]);
+
+            let file = arc.get_file_contents("ui/message/msg_name.msbt", Region::EuFrench).unwrap();
+            assert_eq!(file, b"name EuFrench");
+        }
+    }
+
+    #[test]
+    fn bad_magic() {
+        assert!(ArcFileRef::new(&[0; 0x40]).is_err());
+    }
+
+    #[test]
+    fn truncated_file_system() {
+        let mut data = Vec::new();
+        data.extend_from_slice(&0xABCD_EF98_7654_3210_u64.to_le_bytes());
+        for offset in &[0_u64, 0, 0, 0x1000, 0] {
+            data.extend_from_slice(&offset.to_le_bytes());
+        }
+
+        assert!(ArcFileRef::new(&data).is_err());
+    }
+
+    #[test]
+    fn table_borrow_or_copy() {
+        let bytes: Vec<u8> = (0..0x21).collect();
+
+        let mut tables = TableReader { bytes: &bytes, pos: 0 };
+        let hashes: Cow<[HashToIndex]> = tables.table(4_u32).unwrap();
+        assert!(matches!(hashes, Cow::Borrowed(_)));
+
+        let mut tables = TableReader { bytes: &bytes, pos: 0 };
+        let misaligned = bytes.as_ptr().align_offset(align_of::<StreamData>()) + 1;
+        tables.take(misaligned).unwrap();
+        let datas: Cow<[StreamData]> = tables.table(1_u32).unwrap();
+        assert!(matches!(datas, Cow::Owned(_)));
+        assert_eq!(datas[0].size, u64::from_le_bytes(bytes[misaligned..misaligned + 8].try_into().unwrap()));
+    }
+}
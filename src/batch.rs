@@ -0,0 +1,147 @@
+use std::io::{self, Read, Seek, SeekFrom};
+
+use crate::lookups::{decode_raw, FileMetadata};
+use crate::progress::ProgressCounter;
+use crate::{ArcLookup, Hash40, LookupError, ProgressHook, Region};
+
+/// Reads separated by a gap of at most this many bytes are merged into a single read
+const MAX_GAP: u64 = 0x1_0000;
+
+/// Merged reads will not be grown beyond this size
+const MAX_MERGED_READ: u64 = 0x100_0000;
+
+/// A range of the arc to be read, tagged with whatever the caller needs to process it
+pub(crate) struct ReadRequest<T> {
+    pub(crate) key: T,
+    pub(crate) offset: u64,
+    pub(crate) size: u64,
+}
+
+/// Read all of the requested ranges in order of their offset, merging nearby ranges into a
+/// single read. The reader is not held while `callback` runs, so it is free to make lookups of
+/// its own.
+///
+/// Requests which don't fit within the section of the arc they start in, or whose read fails,
+/// are passed to `callback` as errors without stopping the rest. Only an error returned from
+/// `callback` itself ends the reads early.
+pub(crate) fn read_coalesced<A, T, F>(arc: &A, mut requests: Vec<ReadRequest<T>>, mut callback: F) -> Result<(), LookupError>
+    where A: ArcLookup + ?Sized,
+          F: FnMut(T, Result<&[u8], LookupError>) -> Result<(), LookupError>,
+{
+    let arc_len = arc.get_file_reader().seek(SeekFrom::End(0))?;
+
+    requests.sort_by_key(|request| request.offset);
+
+    let mut buffer = Vec::new();
+    let mut requests = requests.into_iter().peekable();
+    while let Some(first) = requests.next() {
+        // Checked before allocating, so a corrupt size can't be larger than the arc itself
+        if !in_section(arc, &first, arc_len) {
+            callback(first.key, Err(outside_section()))?;
+            continue
+        }
+
+        let start = first.offset;
+        let mut end = first.offset + first.size;
+        let mut span = vec![first];
+
+        while let Some(next) = requests.peek() {
+            if !in_section(arc, next, arc_len) {
+                break
+            }
+
+            let next_end = (next.offset + next.size).max(end);
+            if next.offset > end.saturating_add(MAX_GAP) || next_end - start > MAX_MERGED_READ {
+                break
+            }
+
+            end = next_end;
+            span.extend(requests.next());
+        }
+
+        buffer.resize((end - start) as usize, 0);
+        let read = {
+            let mut reader = arc.get_file_reader();
+            reader.seek(SeekFrom::Start(start)).and_then(|_| reader.read_exact(&mut buffer))
+        };
+
+        match read {
+            Ok(()) => {
+                for request in span {
+                    let relative = (request.offset - start) as usize;
+                    callback(request.key, Ok(&buffer[relative..relative + request.size as usize]))?;
+                }
+            }
+            Err(err) => {
+                for request in span {
+                    callback(request.key, Err(io::Error::new(err.kind(), err.to_string()).into()))?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether a request lies entirely within the section of the arc it starts in
+fn in_section<A: ArcLookup + ?Sized, T>(arc: &A, request: &ReadRequest<T>, arc_len: u64) -> bool {
+    let section_start = [arc.get_stream_section_offset(), arc.get_file_section_offset()]
+        .iter()
+        .copied()
+        .filter(|&offset| offset <= request.offset)
+        .max();
+
+    match section_start {
+        Some(section_start) => {
+            let (_, section_end) = section_bounds(arc, section_start, arc_len);
+            matches!(request.offset.checked_add(request.size), Some(end) if end <= section_end)
+        }
+        None => false,
+    }
+}
+
+fn outside_section() -> LookupError {
+    io::Error::new(io::ErrorKind::UnexpectedEof, "the data lies outside of the section it belongs in").into()
+}
+
+/// A section runs from its offset up until the start of the next section or the end of the arc
+pub(crate) fn section_bounds<A: ArcLookup + ?Sized>(arc: &A, start: u64, arc_len: u64) -> (u64, u64) {
+    let end = [arc.get_stream_section_offset(), arc.get_file_section_offset()]
+        .iter()
+        .copied()
+        .filter(|&offset| offset > start)
+        .fold(arc_len, u64::min);
+
+    (start, end)
+}
+
+pub(crate) fn read_batch<A, F>(arc: &A, hashes: &[Hash40], region: Region, hook: &dyn ProgressHook, mut callback: F) -> Result<(), LookupError>
+    where A: ArcLookup + ?Sized,
+          F: FnMut(Hash40, Result<Vec<u8>, LookupError>),
+{
+    let mut requests = Vec::with_capacity(hashes.len());
+    for &hash in hashes {
+        match arc.get_file_metadata(hash, region) {
+            Ok(metadata) => requests.push(ReadRequest {
+                offset: metadata.offset,
+                size: metadata.comp_size,
+                key: metadata,
+            }),
+            Err(err) => callback(hash, Err(err)),
+        }
+    }
+
+    let bytes_total = requests.iter().map(|request| request.key.decomp_size).sum();
+    let progress = ProgressCounter::new(hook, requests.len() as u64, bytes_total);
+
+    read_coalesced(arc, requests, |metadata: FileMetadata, raw| {
+        progress.check_cancelled()?;
+
+        let data = raw.and_then(|raw| decode_raw(raw, metadata.is_compressed, metadata.uses_zstd, metadata.decomp_size));
+        callback(metadata.path_hash, data);
+
+        progress.file_done(metadata.path_hash, metadata.decomp_size);
+        Ok(())
+    })
+}
+
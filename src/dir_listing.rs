@@ -0,0 +1,106 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::hash_labels::HashLabels;
+use crate::{hash40, FileNode, FileSystem, Hash40};
+
+/// The children of every directory in an arc, pieced together from the labels of its files
+pub(crate) struct DirListing {
+    dirs: HashMap<Hash40, Vec<FileNode>>,
+    /// Labels of the directories which weren't among the labels the listing was generated from
+    parent_labels: HashLabels,
+}
+
+impl DirListing {
+    pub(crate) fn generate(fs: &FileSystem, labels: &HashLabels) -> Self {
+        let mut builder = ListingBuilder {
+            labels,
+            parent_labels: HashLabels::new(),
+            linked: HashSet::new(),
+            entries: Vec::with_capacity(fs.file_paths.len() * 2),
+        };
+
+        for file_path in &fs.file_paths {
+            let dir = file_path.parent.hash40();
+            builder.entries.push((dir, FileNode::File(file_path.path.hash40())));
+
+            if let Some(label) = dir.label(labels) {
+                builder.link_parents(label);
+            }
+        }
+
+        for stream in &fs.stream_hash_to_entries {
+            let path = stream.hash40();
+            if let Some((dir, _)) = path.label(labels).and_then(|label| label.rsplit_once('/')) {
+                builder.entries.push((hash40(dir), FileNode::File(path)));
+                builder.link_parents(dir);
+            }
+        }
+
+        builder.finish()
+    }
+
+    pub(crate) fn dirs(&self) -> &HashMap<Hash40, Vec<FileNode>> {
+        &self.dirs
+    }
+
+    pub(crate) fn get(&self, dir: Hash40) -> Option<&[FileNode]> {
+        self.dirs.get(&dir).map(AsRef::as_ref)
+    }
+
+    pub(crate) fn parent_label(&self, hash: Hash40) -> Option<&str> {
+        hash.label(&self.parent_labels)
+    }
+}
+
+struct ListingBuilder<'a> {
+    labels: &'a HashLabels,
+    parent_labels: HashLabels,
+    /// Directories which have already been linked to their parents
+    linked: HashSet<Hash40>,
+    entries: Vec<(Hash40, FileNode)>,
+}
+
+impl ListingBuilder<'_> {
+    /// Add a directory to its parent, and each parent to its own parent up to the root
+    fn link_parents(&mut self, mut dir: &str) {
+        let mut child = hash40(dir);
+
+        while self.linked.insert(child) {
+            if child.label(self.labels).is_none() {
+                self.parent_labels.add_label(dir);
+            }
+
+            let parent = match dir.trim_end_matches('/').rfind('/') {
+                Some(len) => &dir[..len],
+                None => "/",
+            };
+
+            let hash = hash40(parent);
+            self.entries.push((hash, FileNode::Dir(child)));
+
+            if parent == "/" {
+                break
+            }
+
+            dir = parent;
+            child = hash;
+        }
+    }
+
+    /// Group the entries by directory with a single sort, rather than inserting each one into
+    /// its directory's sorted listing
+    fn finish(mut self) -> DirListing {
+        self.entries.sort_unstable();
+        self.entries.dedup();
+
+        let mut dirs: HashMap<Hash40, Vec<FileNode>> = HashMap::new();
+        for (parent, child) in self.entries {
+            dirs.entry(parent).or_default().push(child);
+        }
+
+        DirListing {
+            dirs,
+            parent_labels: self.parent_labels,
+        }
+    }
+}
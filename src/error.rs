@@ -0,0 +1,65 @@
+use thiserror::Error;
+
+use crate::{LookupError, ValidationError};
+
+/// Any error which can occur while opening or reading from an arc, for propagating errors from
+/// both with `?`
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("failed to parse the arc")]
+    Parse(#[source] binrw::Error),
+
+    #[error(transparent)]
+    Lookup(#[from] LookupError),
+
+    #[error(transparent)]
+    Validation(#[from] ValidationError),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+impl From<binrw::Error> for Error {
+    fn from(err: binrw::Error) -> Self {
+        match err {
+            binrw::Error::Io(err) => Error::Io(err),
+            err => Error::Parse(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Hash40, LookupStep, TableKind};
+
+    fn open_and_read() -> Result<Vec<u8>, Error> {
+        let err = ValidationError::EmptyTable {
+            table: TableKind::FileInfoBuckets,
+            required_by: TableKind::FileHashToPathIndex,
+        };
+
+        Err(err)?
+    }
+
+    #[test]
+    fn validation_errors() {
+        assert!(matches!(open_and_read(), Err(Error::Validation(ValidationError::EmptyTable { .. }))));
+    }
+
+    #[test]
+    fn parse_errors() {
+        let err: Error = binrw::Error::Io(std::io::ErrorKind::UnexpectedEof.into()).into();
+        assert!(matches!(err, Error::Io(_)));
+
+        let err: Error = binrw::Error::AssertFail { pos: 0, message: String::new() }.into();
+        assert!(matches!(err, Error::Parse(_)));
+    }
+
+    #[test]
+    fn lookup_errors() {
+        let err: Error = LookupError::missing(Hash40(0x10), LookupStep::FileBucket).into();
+
+        assert_eq!(err.to_string(), "0x10 could not be found in the file hash buckets");
+    }
+}
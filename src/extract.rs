@@ -0,0 +1,341 @@
+use std::path::{Component, Path, PathBuf};
+
+use rayon::prelude::*;
+
+use crate::batch::{read_coalesced, ReadRequest};
+use crate::lookups::{checked, decode_raw, FileMetadata};
+use crate::progress::ProgressCounter;
+use crate::{hash40, ArcFile, ArcLookup, Hash40, LookupError, ProgressHook, Region, TableKind, WalkdirIter};
+
+/// The maximum amount of compressed data to read from the arc before handing it off to be
+/// decompressed
+const MAX_BATCH_SIZE: u64 = 0x400_0000;
+
+/// The result of extracting files from an arc, see [`ArcFile::extract_dir`]
+#[derive(Debug, Default)]
+pub struct ExtractReport {
+    /// The number of files which were written
+    pub extracted: usize,
+    /// Every file which couldn't be extracted, along with why. Directories which couldn't be
+    /// walked and files without a valid path are reported as `Hash40(0)`.
+    pub failed: Vec<(Hash40, LookupError)>,
+}
+
+impl ExtractReport {
+    /// Whether every file was extracted
+    pub fn is_ok(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    fn add_written(&mut self, written: Vec<WrittenFile>) {
+        for (path, result) in written {
+            match result {
+                Ok(()) => self.extracted += 1,
+                Err(err) => self.failed.push((path, err)),
+            }
+        }
+    }
+}
+
+struct ExtractJob {
+    path: PathBuf,
+    metadata: FileMetadata,
+}
+
+/// A job along with its compressed data, or why the data couldn't be read
+type RawFile = (ExtractJob, Result<Vec<u8>, LookupError>);
+
+/// A file's path along with whether it was written
+type WrittenFile = (Hash40, Result<(), LookupError>);
+
+impl ArcFile {
+    /// Extract every file in the arc into `dest`.
+    ///
+    /// See [`extract_dir`](ArcFile::extract_dir) for details.
+    pub fn extract_all<P: AsRef<Path>>(&self, dest: P, region: Region) -> Result<ExtractReport, LookupError> {
+        self.extract_dir("/", dest, region)
+    }
+
+    /// Equivalent to [`extract_all`](ArcFile::extract_all) while reporting progress to `hook`.
+    pub fn extract_all_with_progress<P: AsRef<Path>>(&self, dest: P, region: Region, hook: &dyn ProgressHook) -> Result<ExtractReport, LookupError> {
+        self.extract_dir_with_progress("/", dest, region, hook)
+    }
+
+    /// Extract every file under a directory into `dest`, keeping the paths of the files relative
+    /// to the root of the arc.
+    ///
+    /// The files are found by walking the arc's directory hierarchy, so labels are only used to
+    /// name them. Files without a label are named after their hash, within their parent
+    /// directory if it has a label. Streams aren't part of the hierarchy, so only extracting
+    /// the whole arc includes them.
+    ///
+    /// Files are read in the order they are stored in the arc and decompressed in parallel. A file
+    /// which can't be looked up, read or written is skipped and listed in the returned report,
+    /// while the rest are still extracted.
+    pub fn extract_dir<Hash: Into<Hash40>, P: AsRef<Path>>(&self, hash: Hash, dest: P, region: Region) -> Result<ExtractReport, LookupError> {
+        self.extract_dir_with_progress(hash, dest, region, &())
+    }
+
+    /// Equivalent to [`extract_dir`](ArcFile::extract_dir) while reporting progress to `hook`.
+    /// Returns [`LookupError::Cancelled`] if the hook cancels the extraction, in which case any
+    /// files already written are left in place.
+    pub fn extract_dir_with_progress<Hash: Into<Hash40>, P: AsRef<Path>>(&self, hash: Hash, dest: P, region: Region, hook: &dyn ProgressHook) -> Result<ExtractReport, LookupError> {
+        let mut report = ExtractReport::default();
+        let mut jobs = Vec::new();
+        self.collect_extract_jobs(hash.into(), dest.as_ref(), region, &mut jobs, &mut report.failed)?;
+
+        jobs.sort_unstable_by_key(|job| job.metadata.offset);
+
+        let bytes_total = jobs.iter().map(|job| job.metadata.decomp_size).sum();
+        let progress = ProgressCounter::new(hook, jobs.len() as u64, bytes_total);
+
+        let mut pending = Vec::new();
+        for batch in batches(jobs) {
+            let (written, read) = rayon::join(
+                || write_files(pending, &progress),
+                || self.read_raw_files(batch),
+            );
+
+            report.add_written(written?);
+            pending = read?;
+            progress.check_cancelled()?;
+        }
+        report.add_written(write_files(pending, &progress)?);
+
+        Ok(report)
+    }
+
+    fn collect_extract_jobs(
+        &self,
+        dir: Hash40,
+        dest: &Path,
+        region: Region,
+        jobs: &mut Vec<ExtractJob>,
+        failed: &mut Vec<(Hash40, LookupError)>,
+    ) -> Result<(), LookupError> {
+        let is_root = dir == hash40("/");
+        let walk = if is_root { self.walk() } else { WalkdirIter::from_hash(self, dir)? };
+
+        for entry in walk {
+            let file_info = match entry {
+                Ok(entry) => entry.file_info(),
+                Err(err) => {
+                    failed.push((Hash40(0), err));
+                    continue
+                }
+            };
+            let file_path = match checked(self.get_file_paths(), usize::from(file_info.file_path_index), TableKind::FilePaths) {
+                Ok(file_path) => file_path,
+                Err(err) => {
+                    failed.push((Hash40(0), err));
+                    continue
+                }
+            };
+            let path = file_path.path.hash40();
+
+            match self.get_file_metadata(path, region) {
+                Ok(metadata) => jobs.push(ExtractJob {
+                    path: self.extract_path(dest, path, Some(file_path.parent.hash40())),
+                    metadata,
+                }),
+                Err(err) => failed.push((path, err)),
+            }
+        }
+
+        if is_root {
+            for stream_entry in self.get_stream_entries() {
+                let path = stream_entry.hash40();
+
+                match self.get_file_metadata(path, region) {
+                    Ok(metadata) => jobs.push(ExtractJob {
+                        path: self.extract_path(dest, path, None),
+                        metadata,
+                    }),
+                    Err(err) => failed.push((path, err)),
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Where to extract a file to, named after its label or its hash if it doesn't have one
+    fn extract_path(&self, dest: &Path, hash: Hash40, parent: Option<Hash40>) -> PathBuf {
+        let label = self.label(hash).map(sanitize_path).filter(|path| path.file_name().is_some());
+
+        match label {
+            Some(path) => dest.join(path),
+            None => {
+                let parent = parent.and_then(|parent| self.label(parent)).map(sanitize_path).unwrap_or_default();
+                dest.join(parent).join(format!("{:#x}", hash.as_u64()))
+            }
+        }
+    }
+
+    fn read_raw_files(&self, jobs: Vec<ExtractJob>) -> Result<Vec<RawFile>, LookupError> {
+        let mut files = Vec::with_capacity(jobs.len());
+        let requests = jobs.into_iter()
+            .map(|job| ReadRequest {
+                offset: job.metadata.offset,
+                size: job.metadata.comp_size,
+                key: job,
+            })
+            .collect();
+
+        read_coalesced(self, requests, |job, raw| {
+            files.push((job, raw.map(<[u8]>::to_vec)));
+            Ok(())
+        })?;
+
+        Ok(files)
+    }
+}
+
+/// Split the jobs into groups of at most `MAX_BATCH_SIZE` bytes of compressed data
+fn batches(jobs: Vec<ExtractJob>) -> Vec<Vec<ExtractJob>> {
+    let mut batches = vec![];
+    let mut batch = vec![];
+    let mut batch_size = 0;
+
+    for job in jobs {
+        if batch_size + job.metadata.comp_size > MAX_BATCH_SIZE && !batch.is_empty() {
+            batches.push(std::mem::take(&mut batch));
+            batch_size = 0;
+        }
+
+        batch_size += job.metadata.comp_size;
+        batch.push(job);
+    }
+
+    if !batch.is_empty() {
+        batches.push(batch);
+    }
+
+    batches
+}
+
+/// Decompress and write out the files, returning the result of writing each one. Only
+/// cancellation stops the whole batch.
+fn write_files(files: Vec<RawFile>, progress: &ProgressCounter) -> Result<Vec<WrittenFile>, LookupError> {
+    files.into_par_iter()
+        .map(|(job, raw)| {
+            progress.check_cancelled()?;
+
+            let metadata = &job.metadata;
+            let written = raw.and_then(|raw| write_file(&job, &raw));
+
+            progress.file_done(metadata.path_hash, metadata.decomp_size);
+            Ok((metadata.path_hash, written))
+        })
+        .collect()
+}
+
+fn write_file(job: &ExtractJob, raw: &[u8]) -> Result<(), LookupError> {
+    let metadata = &job.metadata;
+    let data = decode_raw(raw, metadata.is_compressed, metadata.uses_zstd, metadata.decomp_size)?;
+
+    if let Some(parent) = job.path.parent() {
+        std::fs::create_dir_all(parent).map_err(LookupError::FileWrite)?;
+    }
+
+    std::fs::write(&job.path, data).map_err(LookupError::FileWrite)
+}
+
+/// Convert a label to a relative path, dropping any component which would escape the
+/// destination. Labels are split on both separators, as either is a separator on Windows.
+fn sanitize_path(label: &str) -> PathBuf {
+    label
+        .split(['/', '\\'])
+        .map(|component| component.trim_end_matches(':'))
+        .filter(|component| {
+            let mut components = Path::new(component).components();
+            matches!((components.next(), components.next()), (Some(Component::Normal(_)), None))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_arc::ArcBuilder;
+    use crate::FileInfoIdx;
+
+    #[test]
+    fn extract_dir() {
+        let arc = ArcBuilder::sample().open();
+
+        let dest = std::env::temp_dir().join(format!("smash-arc-test-extract-{}", std::process::id()));
+        let count = arc.extract_dir("fighter/mario", &dest, Region::UsEnglish);
+        let model = std::fs::read(dest.join("fighter/mario/model/body/c00/model.numdlb"));
+        let texture = std::fs::read(dest.join("fighter/mario/model/body/c00/def_mario_001_col.nutexb"));
+        std::fs::remove_dir_all(&dest).unwrap();
+
+        let report = count.unwrap();
+        assert!(report.is_ok(), "{:?}", report.failed);
+        assert_eq!(report.extracted, 4);
+        assert_eq!(model.unwrap(), b"mario model");
+        assert_eq!(texture.unwrap(), [0x55; 0x300]);
+    }
+
+    #[test]
+    fn extract_skips_bad_files() {
+        let mut arc = ArcBuilder::sample().open();
+        let model = hash40("fighter/mario/model/body/c00/model.numdlb");
+        let texture = hash40("fighter/mario/model/body/c00/def_mario_001_col.nutexb");
+
+        let file_info = *arc.get_file_info_from_hash(model).unwrap();
+        arc.get_file_data_mut(&file_info, Region::None).comp_size = u32::MAX;
+        let file_info = arc.get_file_info_from_hash(texture).unwrap().file_info_indice_index;
+        arc.file_system.file_info_indices[usize::from(file_info)].file_info_index = FileInfoIdx(0xFF_FFFF);
+
+        let dest = std::env::temp_dir().join(format!("smash-arc-test-extract-bad-{}", std::process::id()));
+        let report = arc.extract_dir("fighter/mario", &dest, Region::UsEnglish);
+        let eyes = std::fs::read(dest.join("fighter/mario/model/body/c00/leyes_eye_mario_l_col.nutexb"));
+        std::fs::remove_dir_all(&dest).unwrap();
+
+        let report = report.unwrap();
+        assert_eq!(report.extracted, 2);
+        assert_eq!(report.failed.len(), 2);
+        assert!(report.failed.iter().any(|(path, err)| *path == model && matches!(err, LookupError::FileRead(_))));
+        assert!(report.failed.iter().any(|(path, _)| *path == texture));
+        assert_eq!(eyes.unwrap(), b"shared eyes");
+    }
+
+    #[test]
+    fn extract_unlabelled() {
+        let arc = ArcFile::from_reader_with_labels(
+            std::io::Cursor::new(ArcBuilder::sample().build()),
+            crate::HashLabels::from_string("fighter/mario/motion/body/c00"),
+        ).unwrap();
+
+        let dest = std::env::temp_dir().join(format!("smash-arc-test-extract-unlabelled-{}", std::process::id()));
+        let count = arc.extract_dir("fighter/mario", &dest, Region::UsEnglish);
+        let motion = dest
+            .join("fighter/mario/motion/body/c00")
+            .join(format!("{:#x}", hash40("fighter/mario/motion/body/c00/a00wait1.nuanmb").as_u64()));
+        let motion_exists = motion.is_file();
+        let all = arc.extract_all(&dest, Region::UsEnglish);
+        std::fs::remove_dir_all(&dest).unwrap();
+
+        // The hierarchy is found without any labels, and labels are only used for naming
+        assert_eq!(count.unwrap().extracted, 4);
+        assert!(motion_exists);
+        assert_eq!(all.unwrap().extracted, arc.get_file_paths().len() + arc.get_stream_entries().len());
+    }
+
+    #[test]
+    fn sanitize_labels() {
+        assert_eq!(
+            sanitize_path("fighter/mario/model/body/c00/model.numdlb"),
+            Path::new("fighter/mario/model/body/c00/model.numdlb")
+        );
+        assert_eq!(
+            sanitize_path("stream:/sound/bgm/bgm_crs2_01_menu.nus3audio"),
+            Path::new("stream/sound/bgm/bgm_crs2_01_menu.nus3audio")
+        );
+        assert_eq!(sanitize_path("../../etc/passwd"), Path::new("etc/passwd"));
+        assert_eq!(sanitize_path("..\\..\\windows\\./system32"), Path::new("windows/system32"));
+        assert_eq!(sanitize_path("/C:\\Users/../a"), Path::new("C/Users/a"));
+        assert!(sanitize_path("../.").file_name().is_none());
+    }
+}
@@ -7,7 +7,8 @@ use region::Region;
 /// as an opaque pointer
 ///
 /// **Note:** If you want directory listing to work, be sure to set a hashfile using
-/// [`arc_load_labels`](arc_load_labels)
+/// [`arc_load_labels`](arc_load_labels) before opening the arc, or use
+/// [`arc_open_with_labels`](arc_open_with_labels)
 #[no_mangle]
 pub unsafe extern "C" fn arc_open(path: *const i8) -> Option<Box<ArcFile>> {
     let path = std::ffi::CStr::from_ptr(path);
@@ -16,6 +17,22 @@ pub unsafe extern "C" fn arc_open(path: *const i8) -> Option<Box<ArcFile>> {
     Some(Box::new(ArcFile::open(path).ok()?))
 }
 
+/// Open an ArcFile from a given null-terminated path, using the hash labels from the file at
+/// `labels_path` rather than the global labels. Labels can then be retrieved using
+/// [`arc_get_label`](arc_get_label).
+///
+/// **Note:** `Box<ArcFile>` is equivelant in layout to `*mut ArcFile`, but should be treated
+/// as an opaque pointer
+#[no_mangle]
+pub unsafe extern "C" fn arc_open_with_labels(path: *const i8, labels_path: *const i8) -> Option<Box<ArcFile>> {
+    let path = std::ffi::CStr::from_ptr(path);
+    let path = path.to_string_lossy().into_owned();
+    let labels_path = std::ffi::CStr::from_ptr(labels_path);
+    let labels = HashLabels::from_file(labels_path.to_string_lossy().as_ref()).ok()?;
+
+    Some(Box::new(ArcFile::open_with_labels(path, labels).ok()?))
+}
+
 /// Open an ArcFile from a given IP address.
 /// This is intended for use with [arc-network](https://github.com/jam1garner/arc-network).
 ///
@@ -75,7 +92,7 @@ pub extern "C" fn arc_get_file_contents_regional(
 /// Frees the memory allocated by [arc_get_file_contents] or [arc_get_file_contents_regional].
 #[no_mangle]
 pub unsafe extern "C" fn arc_free_file_contents(ffi: FfiBytes) {
-    Box::from_raw(std::slice::from_raw_parts_mut(ffi.ptr, ffi.size));
+    drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut(ffi.ptr, ffi.size)));
 }
 
 /// Get file information such as offset and decompressed size for the given [Hash40].
@@ -113,7 +130,7 @@ pub extern "C" fn arc_get_shared_files_regional(
 /// Free an owned list of shared files
 #[no_mangle]
 pub unsafe extern "C" fn arc_free_shared_file_list(ffi: FfiVec<Hash40>) {
-    Box::from_raw(std::slice::from_raw_parts_mut(ffi.ptr, ffi.size));
+    drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut(ffi.ptr, ffi.size)));
 }
 
 /// Extract a file to a given null-terminated path for the given [Hash40]
@@ -132,7 +149,7 @@ pub unsafe extern "C" fn arc_extract_file(
                 Err(_) => ExtractResult::IoError,
             }
         }
-        Err(lookups::LookupError::Missing) => ExtractResult::Missing,
+        Err(lookups::LookupError::Missing { .. }) => ExtractResult::Missing,
         Err(_) => ExtractResult::IoError,
     }
 }
@@ -154,7 +171,148 @@ pub unsafe extern "C" fn arc_extract_file_regional(
                 Err(_) => ExtractResult::IoError,
             }
         }
-        Err(lookups::LookupError::Missing) => ExtractResult::Missing,
+        Err(lookups::LookupError::Missing { .. }) => ExtractResult::Missing,
+        Err(_) => ExtractResult::IoError,
+    }
+}
+
+/// Decompress a file into a caller-provided buffer of `size` bytes for the given [Hash40]. See
+/// [arc_read_file_into_regional] for details on `buffer`.
+#[no_mangle]
+pub unsafe extern "C" fn arc_read_file_into(
+    arc: &ArcFile,
+    hash: Hash40,
+    buffer: *mut u8,
+    size: usize,
+) -> ExtractResult {
+    arc_read_file_into_regional(arc, hash, buffer, size, Region::UsEnglish)
+}
+
+/// Decompress a file into a caller-provided buffer of `size` bytes for the given [Hash40] for a
+/// given region.
+///
+/// `buffer` may be null, in which case it is treated as empty regardless of `size`: only an empty
+/// file can be read into it, and any other file returns `BufferTooSmall`.
+#[no_mangle]
+pub unsafe extern "C" fn arc_read_file_into_regional(
+    arc: &ArcFile,
+    hash: Hash40,
+    buffer: *mut u8,
+    size: usize,
+    region: Region,
+) -> ExtractResult {
+    let buffer: &mut [u8] = if buffer.is_null() {
+        &mut []
+    } else {
+        std::slice::from_raw_parts_mut(buffer, size)
+    };
+
+    match arc.read_file_into(hash, region, buffer) {
+        Ok(_) => ExtractResult::Ok,
+        Err(lookups::LookupError::Missing { .. }) => ExtractResult::Missing,
+        Err(lookups::LookupError::BufferTooSmall { .. }) => ExtractResult::BufferTooSmall,
+        Err(_) => ExtractResult::IoError,
+    }
+}
+
+/// A callback for reporting the progress of long-running operations. Return `false` to cancel
+/// the operation.
+pub type ProgressCallback = extern "C" fn(progress: &Progress, user_data: *mut std::ffi::c_void) -> bool;
+
+struct FfiProgress {
+    callback: Option<ProgressCallback>,
+    user_data: *mut std::ffi::c_void,
+    cancelled: std::sync::atomic::AtomicBool,
+}
+
+// The caller is responsible for the callback and user data being safe to use from any thread
+unsafe impl Sync for FfiProgress {}
+
+impl FfiProgress {
+    fn new(callback: Option<ProgressCallback>, user_data: *mut std::ffi::c_void) -> Self {
+        Self {
+            callback,
+            user_data,
+            cancelled: Default::default(),
+        }
+    }
+}
+
+impl ProgressHook for FfiProgress {
+    fn on_progress(&self, progress: Progress) {
+        if let Some(callback) = self.callback {
+            if !callback(&progress, self.user_data) {
+                self.cancelled.store(true, std::sync::atomic::Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(std::sync::atomic::Ordering::Relaxed)
+    }
+}
+
+/// Extract every file under a directory to a given null-terminated path for a given region.
+/// `callback` may be null, and is called from multiple threads with `user_data` as files are
+/// extracted. Files which can't be extracted are skipped, in which case
+/// [`ExtractResult::IoError`] is returned once the rest have been written.
+#[cfg(feature = "extract")]
+#[no_mangle]
+pub unsafe extern "C" fn arc_extract_dir(
+    arc: &ArcFile,
+    hash: Hash40,
+    path: *const i8,
+    region: Region,
+    callback: Option<ProgressCallback>,
+    user_data: *mut std::ffi::c_void,
+) -> ExtractResult {
+    let path = std::ffi::CStr::from_ptr(path);
+    let path = path.to_string_lossy().into_owned();
+    let progress = FfiProgress::new(callback, user_data);
+
+    match arc.extract_dir_with_progress(hash, path, region, &progress) {
+        Ok(report) if report.is_ok() => ExtractResult::Ok,
+        Ok(_) => ExtractResult::IoError,
+        Err(lookups::LookupError::Missing { .. }) => ExtractResult::Missing,
+        Err(lookups::LookupError::Cancelled) => ExtractResult::Cancelled,
+        Err(_) => ExtractResult::IoError,
+    }
+}
+
+/// Extract every file in the arc to a given null-terminated path for a given region. See
+/// [arc_extract_dir] for details on `callback`.
+#[cfg(feature = "extract")]
+#[no_mangle]
+pub unsafe extern "C" fn arc_extract_all(
+    arc: &ArcFile,
+    path: *const i8,
+    region: Region,
+    callback: Option<ProgressCallback>,
+    user_data: *mut std::ffi::c_void,
+) -> ExtractResult {
+    arc_extract_dir(arc, hash40::hash40("/"), path, region, callback, user_data)
+}
+
+/// Check every file and stream in the arc for corruption, see [`ArcLookup::verify`]. If
+/// `issue_count` isn't null, it is set to the number of bad entries found. `callback` may be null,
+/// and is called with `user_data` as files are checked.
+#[no_mangle]
+pub extern "C" fn arc_verify(
+    arc: &ArcFile,
+    issue_count: Option<&mut u64>,
+    callback: Option<ProgressCallback>,
+    user_data: *mut std::ffi::c_void,
+) -> ExtractResult {
+    let progress = FfiProgress::new(callback, user_data);
+
+    match arc.verify_with_progress(&progress) {
+        Ok(report) => {
+            if let Some(issue_count) = issue_count {
+                *issue_count = report.issues.len() as u64;
+            }
+            ExtractResult::Ok
+        }
+        Err(lookups::LookupError::Cancelled) => ExtractResult::Cancelled,
         Err(_) => ExtractResult::IoError,
     }
 }
@@ -169,14 +327,25 @@ pub unsafe extern "C" fn arc_load_labels(path: *const i8) -> bool {
     Hash40::set_global_labels_file(path).is_ok()
 }
 
-/// Get a label for a given Hash40 or [std::ptr::null_mut] if no label could be found.
+/// Get a label for a given Hash40 or [std::ptr::null_mut] if no label could be found or it
+/// contains a null byte.
 #[no_mangle]
 pub unsafe extern "C" fn arc_hash40_to_str(hash: Hash40) -> *mut i8 {
     let labels = crate::hash_labels::GLOBAL_LABELS.read();
 
     hash.label(&labels)
-        .map(|string| std::ffi::CString::new(string).unwrap().into_raw())
-        .unwrap_or(std::ptr::null_mut())
+        .and_then(|string| std::ffi::CString::new(string).ok())
+        .map_or(std::ptr::null_mut(), std::ffi::CString::into_raw)
+}
+
+/// Get a label for a given Hash40 from the labels the arc was opened with, or
+/// [std::ptr::null_mut] if no label could be found or it contains a null byte. Must be freed
+/// using [arc_free_str].
+#[no_mangle]
+pub extern "C" fn arc_get_label(arc: &ArcFile, hash: Hash40) -> *mut i8 {
+    arc.label(hash)
+        .and_then(|string| std::ffi::CString::new(string).ok())
+        .map_or(std::ptr::null_mut(), std::ffi::CString::into_raw)
 }
 
 /// Hashes a given string and returns the result as a [Hash40].
@@ -186,7 +355,7 @@ pub unsafe extern "C" fn arc_str_to_hash40(string: *const i8) -> Hash40 {
     hash40::hash40_from_bytes(string.to_bytes())
 }
 
-/// Frees the memory allocated by [arc_hash40_to_str].
+/// Frees the memory allocated by [arc_hash40_to_str] or [arc_get_label].
 #[no_mangle]
 pub unsafe extern "C" fn arc_free_str(string: *mut i8) {
     // this is definitely technically maybe UB depending on the allocator, oops?
@@ -247,6 +416,19 @@ pub extern "C" fn arc_get_file_count(arc: &ArcFile) -> u64 {
     arc.file_system.file_paths.len() as u64 + arc.file_system.stream_entries.len() as u64
 }
 
+/// Get an owned list of the metadata of every file counted by [arc_get_file_count] for a
+/// specified region. Files which can't be read due to a corrupt index are skipped.
+#[no_mangle]
+pub extern "C" fn arc_get_files(arc: &ArcFile, region: Region) -> FfiVec<FileMetadata> {
+    Some(arc.files(region).filter_map(Result::ok).collect()).into()
+}
+
+/// Free an owned list of file metadata
+#[no_mangle]
+pub unsafe extern "C" fn arc_free_file_list(ffi: FfiVec<FileMetadata>) {
+    drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut(ffi.ptr, ffi.size)));
+}
+
 /// Generates a cache to aid in faster fuzzy searching
 #[cfg(feature = "search")]
 #[no_mangle]
@@ -254,7 +436,21 @@ pub extern "C" fn arc_generate_search_cache(arc: &ArcFile) -> Box<crate::search:
     Box::new(arc.generate_search_cache())
 }
 
-/// Searches for a given path. Requires labels to be loaded and a search cache be generated
+/// Generates a search cache, see [arc_generate_search_cache]. `callback` may be null, and is
+/// called with `user_data` as files are processed. Returns null if the callback cancels it.
+#[cfg(feature = "search")]
+#[no_mangle]
+pub extern "C" fn arc_generate_search_cache_with_progress(
+    arc: &ArcFile,
+    callback: Option<ProgressCallback>,
+    user_data: *mut std::ffi::c_void,
+) -> Option<Box<crate::search::SearchCache>> {
+    let progress = FfiProgress::new(callback, user_data);
+
+    arc.generate_search_cache_with_progress(&progress).ok().map(Box::new)
+}
+
+/// Searches for a given path using the labels of the arc the search cache was generated from
 #[cfg(feature = "search")]
 #[no_mangle]
 pub unsafe extern "C" fn arc_search_files(
@@ -264,9 +460,8 @@ pub unsafe extern "C" fn arc_search_files(
 ) -> FfiVec<Hash40> {
     let term = std::ffi::CStr::from_ptr(search_term);
     let term = term.to_string_lossy();
-    let labels = crate::hash_labels::GLOBAL_LABELS.read();
 
-    Some(cache.search(&term, &labels, max_files)).into()
+    Some(cache.search(&term, cache.labels(), max_files)).into()
 }
 
 #[repr(u8)]
@@ -274,6 +469,8 @@ pub enum ExtractResult {
     Ok = 0,
     IoError = 1,
     Missing = 2,
+    BufferTooSmall = 3,
+    Cancelled = 4,
 }
 
 type FfiBytes = FfiVec<u8>;
@@ -335,3 +532,27 @@ impl From<Option<&[FileNode]>> for DirListing {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_arc::ArcBuilder;
+
+    #[test]
+    fn labels_with_nul() {
+        let arc = ArcFile::from_reader_with_labels(
+            std::io::Cursor::new(ArcBuilder::sample().build()),
+            HashLabels::from_string("fighter\0mario\nfighter/mario"),
+        ).unwrap();
+
+        // A label which can't be a C string is reported as missing rather than panicking
+        assert!(arc_get_label(&arc, hash40::hash40("fighter\0mario")).is_null());
+
+        let label = arc_get_label(&arc, hash40::hash40("fighter/mario"));
+        assert!(!label.is_null());
+        unsafe {
+            assert_eq!(std::ffi::CStr::from_ptr(label).to_str(), Ok("fighter/mario"));
+            arc_free_str(label);
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use std::io::{self, Read, Seek, SeekFrom};
+
+use crate::lookups::FileMetadata;
+use crate::{LookupError, SeekRead};
+
+type RawReader<'a> = io::Take<Box<dyn SeekRead + 'a>>;
+
+/// A reader over the contents of a single file within the arc, decompressing the file as it is
+/// read rather than all at once.
+///
+/// Returned by [`ArcLookup::open_file`](crate::ArcLookup::open_file).
+pub struct FileReader<'a>(FileReaderKind<'a>);
+
+enum FileReaderKind<'a> {
+    Raw(RawReader<'a>),
+    Zstd(Box<crate::zstd_backend::Decoder<RawReader<'a>>>),
+}
+
+impl<'a> FileReader<'a> {
+    pub(crate) fn new(mut reader: Box<dyn SeekRead + 'a>, metadata: &FileMetadata) -> Result<Self, LookupError> {
+        if metadata.is_compressed && !metadata.uses_zstd {
+            return Err(LookupError::UnsupportedCompression)
+        }
+
+        reader.seek(SeekFrom::Start(metadata.offset))?;
+        let reader = reader.take(metadata.comp_size);
+
+        if metadata.is_compressed {
+            Ok(Self(FileReaderKind::Zstd(Box::new(crate::zstd_backend::decoder(reader)?))))
+        } else {
+            Ok(Self(FileReaderKind::Raw(reader)))
+        }
+    }
+}
+
+impl Read for FileReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match &mut self.0 {
+            FileReaderKind::Raw(reader) => reader.read(buf),
+            FileReaderKind::Zstd(reader) => reader.read(buf),
+        }
+    }
+}
+
+/// A seekable reader over the contents of a single uncompressed or stream file within the arc.
+///
+/// Returned by [`ArcLookup::open_file_seekable`](crate::ArcLookup::open_file_seekable). Seeking
+/// is relative to the start of the file, not the arc.
+pub struct FileSeeker<'a> {
+    reader: Box<dyn SeekRead + 'a>,
+    start: u64,
+    len: u64,
+    pos: u64,
+}
+
+impl<'a> FileSeeker<'a> {
+    pub(crate) fn new(mut reader: Box<dyn SeekRead + 'a>, metadata: &FileMetadata) -> Result<Self, LookupError> {
+        if metadata.is_compressed {
+            return Err(LookupError::NotSeekable)
+        }
+
+        reader.seek(SeekFrom::Start(metadata.offset))?;
+
+        Ok(Self {
+            reader,
+            start: metadata.offset,
+            len: metadata.comp_size,
+            pos: 0,
+        })
+    }
+
+    /// The size of the file in bytes
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl Read for FileSeeker<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.len.saturating_sub(self.pos);
+        let max = (buf.len() as u64).min(remaining) as usize;
+
+        let count = self.reader.read(&mut buf[..max])?;
+        self.pos += count as u64;
+
+        Ok(count)
+    }
+}
+
+impl Seek for FileSeeker<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(pos) => Some(pos),
+            SeekFrom::End(offset) => offset_by(self.len, offset),
+            SeekFrom::Current(offset) => offset_by(self.pos, offset),
+        };
+
+        let invalid = || io::Error::new(io::ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position");
+        let pos = pos.ok_or_else(invalid)?;
+        let arc_pos = self.start.checked_add(pos).ok_or_else(invalid)?;
+
+        self.reader.seek(SeekFrom::Start(arc_pos))?;
+        self.pos = pos;
+
+        Ok(pos)
+    }
+}
+
+fn offset_by(base: u64, offset: i64) -> Option<u64> {
+    if offset < 0 {
+        base.checked_sub(offset.unsigned_abs())
+    } else {
+        base.checked_add(offset as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Hash40;
+
+    fn metadata(offset: u64, size: u64) -> FileMetadata {
+        FileMetadata {
+            path_hash: Hash40(0),
+            ext_hash: Hash40(0),
+            parent_hash: Hash40(0),
+            file_name_hash: Hash40(0),
+            offset,
+            comp_size: size,
+            decomp_size: size,
+            is_stream: true,
+            is_shared: false,
+            is_redirect: false,
+            is_regional: false,
+            is_localized: false,
+            is_compressed: false,
+            uses_zstd: false,
+        }
+    }
+
+    #[test]
+    fn seeker_stays_within_file() {
+        let data: Vec<u8> = (0..32).collect();
+        let mut file = FileSeeker::new(Box::new(io::Cursor::new(&data)), &metadata(8, 16)).unwrap();
+
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents).unwrap();
+        assert_eq!(contents, &data[8..24]);
+
+        file.seek(SeekFrom::End(-4)).unwrap();
+        let mut tail = Vec::new();
+        file.read_to_end(&mut tail).unwrap();
+        assert_eq!(tail, &data[20..24]);
+
+        assert!(file.seek(SeekFrom::Current(-20)).is_err());
+
+        let err = file.seek(SeekFrom::Start(u64::MAX - 4)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn reader_stops_at_file_end() {
+        let data: Vec<u8> = (0..32).collect();
+        let mut file = FileReader::new(Box::new(io::Cursor::new(&data)), &metadata(4, 8)).unwrap();
+
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents).unwrap();
+        assert_eq!(contents, &data[4..12]);
+    }
+}
@@ -0,0 +1,92 @@
+use std::ops::Range;
+
+use crate::lookups::{checked, file_metadata, stream_metadata};
+use crate::{ArcLookup, FileMetadata, FilePathIdx, LookupError, Region, TableKind};
+
+/// An iterator over every file in the arc, created by [`ArcLookup::files`].
+///
+/// Every file path is yielded first, followed by every stream entry, which together are the
+/// files counted by `arc_get_file_count`. A file whose metadata can't be found because of a
+/// corrupt index is yielded as an error, after which iteration continues with the next file.
+pub struct FilesIter<'a, A: ?Sized> {
+    arc: &'a A,
+    region: Region,
+    file_paths: Range<usize>,
+    stream_entries: Range<usize>,
+}
+
+impl<'a, A: ArcLookup + ?Sized> FilesIter<'a, A> {
+    pub fn new(arc: &'a A, region: Region) -> Self {
+        Self {
+            arc,
+            region,
+            file_paths: 0..arc.get_file_paths().len(),
+            stream_entries: 0..arc.get_stream_entries().len(),
+        }
+    }
+
+    fn stream_metadata(&self, index: usize) -> Result<FileMetadata, LookupError> {
+        let stream_entry = &self.arc.get_stream_entries()[index];
+        let data_index = *checked(self.arc.get_stream_file_indices(), stream_entry.index() as usize, TableKind::StreamFileIndices)?;
+        let stream_data = checked(self.arc.get_stream_datas(), data_index as usize, TableKind::StreamDatas)?;
+
+        Ok(stream_metadata(stream_entry.hash40(), stream_data))
+    }
+}
+
+impl<'a, A: ArcLookup + ?Sized> Iterator for FilesIter<'a, A> {
+    type Item = Result<FileMetadata, LookupError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(index) = self.file_paths.next() {
+            return Some(file_metadata(self.arc, FilePathIdx(index as u32), self.region))
+        }
+
+        let index = self.stream_entries.next()?;
+
+        Some(self.stream_metadata(index))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.file_paths.len() + self.stream_entries.len();
+
+        (len, Some(len))
+    }
+}
+
+impl<A: ArcLookup + ?Sized> ExactSizeIterator for FilesIter<'_, A> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_arc::ArcBuilder;
+    use crate::{hash40, ArcFileRef};
+
+    #[test]
+    fn every_file() {
+        let data = ArcBuilder::sample().build();
+        let arc = ArcFileRef::new(&data).unwrap();
+
+        let files: Vec<_> = arc.files(Region::UsEnglish).map(Result::unwrap).collect();
+        assert_eq!(files.len(), arc.get_file_paths().len() + arc.get_stream_entries().len());
+        assert_eq!(files.iter().filter(|file| file.is_stream).count(), arc.get_stream_entries().len());
+
+        for file in &files {
+            let expected = arc.get_file_metadata(file.path_hash, Region::UsEnglish).unwrap();
+            assert_eq!((file.offset, file.decomp_size, file.is_stream), (expected.offset, expected.decomp_size, expected.is_stream));
+        }
+
+        let msbt = files.iter().find(|file| file.path_hash == hash40("ui/message/msg_name.msbt")).unwrap();
+        assert!(msbt.is_regional);
+        assert_eq!(msbt.decomp_size, b"name UsEnglish".len() as u64);
+    }
+
+    #[test]
+    fn corrupt_stream_index() {
+        let mut arc = ArcBuilder::sample().open();
+        arc.file_system.stream_file_indices[0] = 0xFFFF;
+
+        let errors: Vec<_> = arc.files(Region::None).filter_map(Result::err).collect();
+        assert!(matches!(errors[..], [LookupError::CorruptIndex { table: TableKind::StreamDatas, .. }]));
+    }
+}
@@ -1,7 +1,11 @@
 use crate::{FileDataIdx, FileInfoIdx, FileInfoIndiceIdx, FilePathIdx, Hash40, InfoToDataIdx};
 use modular_bitfield::prelude::*;
 
-use binrw::{binread, io::*, BinRead, BinResult, ReadOptions};
+use binrw::{binread, io::*, BinRead, BinResult, ReadOptions, VecArgs};
+
+/// The largest compressed or decompressed file system table which will be read. Larger sizes in
+/// a [`CompTableHeader`] are treated as corruption rather than allocated.
+pub const MAX_TABLE_SIZE: u32 = 0x1000_0000;
 
 #[derive(BinRead, Debug, Clone, Copy)]
 #[br(magic = 0x10_u32)]
@@ -20,17 +24,62 @@ impl BinRead for CompressedFileSystem {
     where
         R: Read + Seek,
     {
+        let pos = reader.stream_position()?;
         let header = CompTableHeader::read_options(reader, options, args)?;
 
-        let mut compressed = vec![0; header.comp_size as usize];
+        if header.comp_size > MAX_TABLE_SIZE || header.decomp_size > MAX_TABLE_SIZE {
+            return Err(binrw::Error::AssertFail {
+                pos,
+                message: format!(
+                    "file system table of {:#x} bytes ({:#x} decompressed) is larger than the maximum of {:#x}",
+                    header.comp_size, header.decomp_size, MAX_TABLE_SIZE
+                ),
+            })
+        }
+
+        // Read through `take` so a truncated arc fails before the full size is allocated
+        let mut compressed = Vec::new();
+        reader.take(header.comp_size as u64).read_to_end(&mut compressed)?;
 
-        reader.read_exact(&mut compressed)?;
+        if compressed.len() != header.comp_size as usize {
+            return Err(Error::from(ErrorKind::UnexpectedEof).into())
+        }
 
-        let compressed = Cursor::new(compressed);
-        let mut decompressed = Cursor::new(crate::zstd_backend::decode_all(compressed)?);
+        let mut decompressed = Vec::new();
+        crate::zstd_backend::copy_decode_bounded(&compressed[..], &mut decompressed, header.decomp_size as u64)?;
+
+        FileSystem::read_options(&mut Cursor::new(decompressed), options, ()).map(CompressedFileSystem)
+    }
+}
 
-        FileSystem::read_options(&mut decompressed, options, ()).map(CompressedFileSystem)
+/// Read a table of `count` entries, first checking that it ends within both the data and the
+/// table size given by the [`FileSystemHeader`] so a corrupt count can't cause a huge allocation
+fn read_table<R, T>(reader: &mut R, options: &ReadOptions, (count, table_size): (u64, u32)) -> BinResult<Vec<T>>
+where
+    R: Read + Seek,
+    T: TableEntry,
+{
+    let pos = reader.stream_position()?;
+    let data_len = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(pos))?;
+
+    let table_end = data_len.min(table_size as u64);
+    let end = count
+        .checked_mul(T::SIZE as u64)
+        .and_then(|len| len.checked_add(pos));
+    let fits = matches!(end, Some(end) if end <= table_end);
+
+    if !fits {
+        return Err(binrw::Error::AssertFail {
+            pos,
+            message: format!(
+                "table of {} entries extends past the end of the file system table at {:#x}",
+                count, table_end
+            ),
+        })
     }
+
+    Vec::read_options(reader, options, VecArgs { count: count as usize, inner: () })
 }
 
 /// The filesystem itself. Includes all the linking between paths, file data, directories, and
@@ -43,19 +92,19 @@ pub struct FileSystem {
     #[br(align_before = 0x100)]
     pub stream_header: StreamHeader,
 
-    #[br(count = stream_header.quick_dir_count)]
+    #[br(parse_with = read_table, args(stream_header.quick_dir_count as u64, fs_header.table_filesize))]
     pub quick_dirs: Vec<QuickDir>,
 
-    #[br(count = stream_header.stream_hash_count)]
+    #[br(parse_with = read_table, args(stream_header.stream_hash_count as u64, fs_header.table_filesize))]
     pub stream_hash_to_entries: Vec<HashToIndex>,
 
-    #[br(count = stream_header.stream_hash_count)]
+    #[br(parse_with = read_table, args(stream_header.stream_hash_count as u64, fs_header.table_filesize))]
     pub stream_entries: Vec<StreamEntry>,
 
-    #[br(count = stream_header.stream_file_index_count)]
+    #[br(parse_with = read_table, args(stream_header.stream_file_index_count as u64, fs_header.table_filesize))]
     pub stream_file_indices: Vec<u32>,
 
-    #[br(count = stream_header.stream_offset_entry_count)]
+    #[br(parse_with = read_table, args(stream_header.stream_offset_entry_count as u64, fs_header.table_filesize))]
     pub stream_datas: Vec<StreamData>,
 
     #[br(temp)]
@@ -64,37 +113,37 @@ pub struct FileSystem {
     #[br(temp)]
     pub bucket_count: u32,
 
-    #[br(count = bucket_count)]
+    #[br(parse_with = read_table, args(bucket_count as u64, fs_header.table_filesize))]
     pub file_info_buckets: Vec<FileInfoBucket>,
 
-    #[br(count = hash_index_group_count)]
+    #[br(parse_with = read_table, args(hash_index_group_count as u64, fs_header.table_filesize))]
     pub file_hash_to_path_index: Vec<HashToIndex>,
 
-    #[br(count = fs_header.file_info_path_count)]
+    #[br(parse_with = read_table, args(fs_header.file_info_path_count as u64, fs_header.table_filesize))]
     pub file_paths: Vec<FilePath>,
 
-    #[br(count = fs_header.file_info_index_count)]
+    #[br(parse_with = read_table, args(fs_header.file_info_index_count as u64, fs_header.table_filesize))]
     pub file_info_indices: Vec<FileInfoIndex>,
 
-    #[br(count = fs_header.folder_count)]
+    #[br(parse_with = read_table, args(fs_header.folder_count as u64, fs_header.table_filesize))]
     pub dir_hash_to_info_index: Vec<HashToIndex>,
 
-    #[br(count = fs_header.folder_count)]
+    #[br(parse_with = read_table, args(fs_header.folder_count as u64, fs_header.table_filesize))]
     pub dir_infos: Vec<DirInfo>,
 
-    #[br(count = fs_header.folder_offset_count_1 + fs_header.folder_offset_count_2 + fs_header.extra_folder)]
+    #[br(parse_with = read_table, args(fs_header.folder_offset_count_1 as u64 + fs_header.folder_offset_count_2 as u64 + fs_header.extra_folder as u64, fs_header.table_filesize))]
     pub folder_offsets: Vec<DirectoryOffset>,
 
-    #[br(count = fs_header.hash_folder_count)]
+    #[br(parse_with = read_table, args(fs_header.hash_folder_count as u64, fs_header.table_filesize))]
     pub folder_child_hashes: Vec<HashToIndex>,
 
-    #[br(count = fs_header.file_info_count + fs_header.file_data_count_2 + fs_header.extra_count)]
+    #[br(parse_with = read_table, args(fs_header.file_info_count as u64 + fs_header.file_data_count_2 as u64 + fs_header.extra_count as u64, fs_header.table_filesize))]
     pub file_infos: Vec<FileInfo>,
 
-    #[br(count = fs_header.file_info_sub_index_count + fs_header.file_data_count_2 + fs_header.extra_count_2)]
+    #[br(parse_with = read_table, args(fs_header.file_info_sub_index_count as u64 + fs_header.file_data_count_2 as u64 + fs_header.extra_count_2 as u64, fs_header.table_filesize))]
     pub file_info_to_datas: Vec<FileInfoToFileData>,
 
-    #[br(count = fs_header.file_data_count + fs_header.file_data_count_2 + fs_header.extra_count)]
+    #[br(parse_with = read_table, args(fs_header.file_data_count as u64 + fs_header.file_data_count_2 as u64 + fs_header.extra_count as u64, fs_header.table_filesize))]
     pub file_datas: Vec<FileData>,
 }
 
@@ -142,7 +191,7 @@ pub struct StreamHeader {
 }
 
 #[bitfield]
-#[derive(BinRead, Debug, Clone, Copy)]
+#[derive(BinRead, Debug, Clone, Copy, Default)]
 #[br(map = Self::from_bytes)]
 pub struct QuickDir {
     pub hash: u32,
@@ -152,7 +201,7 @@ pub struct QuickDir {
 }
 
 #[bitfield]
-#[derive(BinRead, Debug, Clone, Copy)]
+#[derive(BinRead, Debug, Clone, Copy, Default)]
 #[br(map = Self::from_bytes)]
 pub struct StreamEntry {
     pub hash: u32,
@@ -161,6 +210,22 @@ pub struct StreamEntry {
     pub flags: u32,
 }
 
+impl StreamEntry {
+    /// The entry's flags, decoded
+    pub fn stream_flags(&self) -> StreamFlags {
+        StreamFlags::from_bytes(self.flags().to_le_bytes())
+    }
+}
+
+/// The flags of a [`StreamEntry`]. Only the bits which have been observed to be set are named.
+#[bitfield]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct StreamFlags {
+    pub is_localized: bool,
+    pub is_regional: bool,
+    pub unk: B30,
+}
+
 #[bitfield]
 #[derive(BinRead, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
 #[br(map = Self::from_bytes)]
@@ -176,6 +241,7 @@ impl PartialEq<Hash40> for HashToIndex {
     }
 }
 
+#[repr(C)]
 #[derive(BinRead, Debug, Clone, Copy)]
 pub struct FileInfoBucket {
     pub start: u32,
@@ -220,8 +286,16 @@ pub enum RedirectionType {
     Shared(DirectoryOffset),
 }
 
+/// A [`RedirectionType`] borrowed from the arc, see
+/// [`try_get_directory_dependency_ref`](crate::ArcLookup::try_get_directory_dependency_ref)
+#[derive(Debug, Clone, Copy)]
+pub enum RedirectionRef<'a> {
+    Symlink(&'a DirInfo),
+    Shared(&'a DirectoryOffset),
+}
+
 #[bitfield]
-#[derive(BinRead, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(BinRead, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
 #[br(map = Self::from_bytes)]
 pub struct DirInfoFlags {
     pub unk1: B26,
@@ -231,6 +305,7 @@ pub struct DirInfoFlags {
     pub unk3: B3,
 }
 
+#[repr(C)]
 #[derive(BinRead, Debug, Clone, Copy)]
 pub struct StreamData {
     pub size: u64,
@@ -238,7 +313,7 @@ pub struct StreamData {
 }
 
 /// Also known as MassLoadingData
-#[repr(packed)]
+#[repr(C, packed)]
 #[derive(BinRead, Debug, Clone, Copy)]
 pub struct DirectoryOffset {
     pub offset: u64,
@@ -263,7 +338,7 @@ pub struct FileInfo {
 }
 
 #[bitfield]
-#[derive(BinRead, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(BinRead, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
 #[br(map = Self::from_bytes)]
 pub struct FileInfoFlags {
     pub unused: B4,
@@ -288,7 +363,7 @@ pub struct FileInfoToFileData {
 }
 
 #[bitfield]
-#[derive(BinRead, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(BinRead, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
 #[br(map = Self::from_bytes)]
 pub struct FileInfoToFileDataBitfield {
     pub file_info_idx: B24,
@@ -305,7 +380,7 @@ pub struct FileData {
 }
 
 #[bitfield]
-#[derive(BinRead, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(BinRead, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
 #[br(map = Self::from_bytes)]
 pub struct FileDataFlags {
     pub compressed: bool,
@@ -437,3 +512,133 @@ impl FolderPathListEntry {
         self.ext.set_hash(idx & 0xFF_FFFF)
     }
 }
+
+/// A type which can be read directly out of the file system tables.
+///
+/// # Safety
+///
+/// Implementors must consist only of plain integers (or bitfields of them) such that any bit
+/// pattern is valid, and `SIZE` must be the size of the entry within the file. Structs must be
+/// `#[repr(C)]` so their fields are laid out in the same order as the file; `impl_table_entry!`
+/// checks the offset of each field at compile time.
+pub(crate) unsafe trait TableEntry: BinRead<Args = ()> + Clone {
+    const SIZE: usize;
+}
+
+/// Implement [`TableEntry`], asserting at compile time that the in-memory layout matches the
+/// file. Entries listed without fields (integers and bitfields, which are a single byte array)
+/// must be exactly `SIZE` bytes. Structs list the offset of every field within the file, and
+/// may only be larger than `SIZE` due to trailing padding, in which case they are never
+/// borrowed from the buffer.
+macro_rules! impl_table_entry {
+    ($($ty:ty = $size:literal $({ $($field:ident: $offset:literal),* $(,)? })?),* $(,)?) => {
+        $(
+            unsafe impl TableEntry for $ty {
+                const SIZE: usize = $size;
+            }
+
+            impl_table_entry!(@layout $ty, $size $(, { $($field: $offset),* })?);
+        )*
+    };
+    (@layout $ty:ty, $size:literal) => {
+        const _: () = assert!(std::mem::size_of::<$ty>() == $size);
+    };
+    (@layout $ty:ty, $size:literal, { $($field:ident: $offset:literal),* }) => {
+        const _: () = {
+            assert!(std::mem::size_of::<$ty>() >= $size);
+            $(assert!(std::mem::offset_of!($ty, $field) == $offset);)*
+        };
+    };
+}
+
+impl_table_entry! {
+    u32 = 4,
+    QuickDir = 12,
+    StreamEntry = 12,
+    HashToIndex = 8,
+    StreamData = 16 { size: 0, offset: 8 },
+    FileInfoBucket = 8 { start: 0, count: 4 },
+    FilePath = 32 { path: 0, ext: 8, parent: 16, file_name: 24 },
+    FileInfoIndex = 8 { dir_offset_index: 0, file_info_index: 4 },
+    DirInfo = 52 {
+        path: 0,
+        name: 8,
+        parent: 16,
+        extra_dis_re: 24,
+        extra_dis_re_length: 28,
+        file_info_start_index: 32,
+        file_count: 36,
+        child_dir_start_index: 40,
+        child_dir_count: 44,
+        flags: 48,
+    },
+    DirectoryOffset = 28 {
+        offset: 0,
+        decomp_size: 8,
+        size: 12,
+        file_start_index: 16,
+        file_count: 20,
+        directory_index: 24,
+    },
+    FileInfo = 16 { file_path_index: 0, file_info_indice_index: 4, info_to_data_index: 8, flags: 12 },
+    FileInfoToFileData = 12 { folder_offset_index: 0, file_data_index: 4, file_info_index_and_load_type: 8 },
+    FileData = 16 { offset_in_folder: 0, comp_size: 4, decomp_size: 8, flags: 12 },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_arc::ArcBuilder;
+    use crate::{ArcFile, ArcFileRef};
+    use std::convert::TryInto;
+
+    fn parse(data: Vec<u8>) -> BinResult<ArcFile> {
+        ArcFile::from_reader(Cursor::new(data))
+    }
+
+    fn is_assert_fail<T>(result: BinResult<T>) -> bool {
+        matches!(result.as_ref().map_err(binrw::Error::root_cause), Err(binrw::Error::AssertFail { .. }))
+    }
+
+    fn is_io_error<T>(result: BinResult<T>) -> bool {
+        matches!(result.as_ref().map_err(binrw::Error::root_cause), Err(binrw::Error::Io(_)))
+    }
+
+    #[test]
+    fn oversized_counts() {
+        // The file path count and the quick dir count
+        for &offset in &[0x4, 0x100] {
+            let data = ArcBuilder::sample().patch_table(offset, &u32::MAX.to_le_bytes()).build();
+            assert!(is_assert_fail(parse(data.clone())));
+            assert!(is_assert_fail(ArcFileRef::new(&data)));
+        }
+
+        // A table size which ends before the tables do
+        let data = ArcBuilder::sample().patch_table(0, &0x120_u32.to_le_bytes()).build();
+        assert!(is_assert_fail(parse(data)));
+    }
+
+    #[test]
+    fn oversized_table() {
+        let data = ArcBuilder::sample().build();
+        let header = u64::from_le_bytes(data[0x20..0x28].try_into().unwrap()) as usize;
+        let decomp_size = u32::from_le_bytes(data[header + 4..header + 8].try_into().unwrap());
+
+        let with_sizes = |decomp_size: u32, comp_size: Option<u32>| {
+            let mut data = data.clone();
+            data[header + 4..header + 8].copy_from_slice(&decomp_size.to_le_bytes());
+            if let Some(comp_size) = comp_size {
+                data[header + 8..header + 12].copy_from_slice(&comp_size.to_le_bytes());
+            }
+            parse(data)
+        };
+
+        assert!(with_sizes(decomp_size, None).is_ok());
+        assert!(is_assert_fail(with_sizes(u32::MAX, None)));
+        assert!(is_assert_fail(with_sizes(decomp_size, Some(MAX_TABLE_SIZE + 1))));
+
+        // The table decompresses to more than it claims, or is cut off
+        assert!(is_io_error(with_sizes(decomp_size - 1, None)));
+        assert!(is_io_error(with_sizes(decomp_size, Some(MAX_TABLE_SIZE))));
+    }
+}
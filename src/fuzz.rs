@@ -0,0 +1,75 @@
+//! Entry points for fuzzing the parser, enabled by the `fuzzing` feature. The cargo-fuzz targets
+//! in `fuzz/` are thin wrappers around these.
+
+use std::io::Cursor;
+
+use crate::{ArcFile, ArcFileRef, ArcLookup, Region};
+
+/// Parse `data` as both an [`ArcFile`] and an [`ArcFileRef`], then look up every file of any arc
+/// which passes [validation](ArcLookup::validate). Errors are expected, panics and unbounded
+/// allocations are bugs.
+pub fn parse_arc(data: &[u8]) {
+    if let Ok(arc) = ArcFile::from_reader(Cursor::new(data.to_vec())) {
+        exercise(&arc);
+    }
+
+    if let Ok(arc) = ArcFileRef::new(data) {
+        exercise(&arc);
+    }
+}
+
+fn exercise<A: ArcLookup>(arc: &A) {
+    if arc.validate().is_err() {
+        return
+    }
+
+    for file_path in arc.get_file_paths() {
+        let _ = arc.get_file_metadata(file_path.path.hash40(), Region::UsEnglish);
+        let _ = arc.resolve(file_path.path.hash40(), Region::UsEnglish);
+    }
+
+    for dir_info in arc.get_dir_infos() {
+        let _ = arc.get_directory_dependency(dir_info);
+        let _ = arc.resolve_dir(dir_info.path.hash40());
+    }
+
+    for stream in arc.get_stream_hash_to_entries() {
+        let _ = arc.get_file_metadata(stream.hash40(), Region::None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_arc::ArcBuilder;
+    use std::convert::TryInto;
+
+    #[test]
+    fn mutated_tables() {
+        let table_len = {
+            let data = ArcBuilder::sample().build();
+            let fs_offset = u64::from_le_bytes(data[0x20..0x28].try_into().unwrap()) as usize;
+            u32::from_le_bytes(data[fs_offset + 4..fs_offset + 8].try_into().unwrap()) as usize
+        };
+
+        // A simple xorshift, so failures are reproducible
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        for _ in 0..500 {
+            let mut builder = ArcBuilder::sample();
+            for _ in 0..(next() % 8 + 1) {
+                let offset = (next() as usize) % (table_len - 4);
+                builder = builder.patch_table(offset, &next().to_le_bytes()[..(next() % 4 + 1) as usize]);
+            }
+
+            parse_arc(&builder.build());
+            parse_arc(&builder.uncompressed_table().build());
+        }
+    }
+}
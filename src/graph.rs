@@ -0,0 +1,333 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use crate::lookups::{checked, checked_range};
+use crate::{
+    ArcLookup, FileDataIdx, FileInfo, Hash40, HashLabels, LookupError, RedirectionRef, Region, ReverseIndex, TableKind,
+};
+
+/// A graph of how the contents of an arc depend on each other, for finding out what else is
+/// affected by replacing a file. Created by [`ArcFile::dependency_graph`](crate::ArcFile::dependency_graph)
+/// or [`generate`](DependencyGraph::generate).
+///
+/// Only file datas used by more than one path are included. Every directory is linked to the
+/// mass-loading group it loads its own files from, along with the shared file group it redirects
+/// to, if any.
+#[derive(Debug, Default)]
+pub struct DependencyGraph {
+    pub edges: Vec<GraphEdge>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum GraphNode {
+    File(Hash40),
+    Dir(Hash40),
+    /// A mass-loading group, by its index in the folder offsets
+    Group(usize),
+    FileData(FileDataIdx),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GraphEdge {
+    pub from: GraphNode,
+    pub to: GraphNode,
+    pub kind: GraphEdgeKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphEdgeKind {
+    /// A file uses a file data which other files use too
+    UsesData,
+    /// A directory is a symlink to another directory
+    Symlink,
+    /// A directory loads its own files from a mass-loading group
+    OwnGroup,
+    /// A directory redirects to a shared file group
+    SharedGroup,
+    /// A file group contains a file
+    Contains,
+}
+
+impl GraphEdgeKind {
+    fn name(self) -> &'static str {
+        match self {
+            GraphEdgeKind::UsesData => "uses_data",
+            GraphEdgeKind::Symlink => "symlink",
+            GraphEdgeKind::OwnGroup => "own_group",
+            GraphEdgeKind::SharedGroup => "shared_group",
+            GraphEdgeKind::Contains => "contains",
+        }
+    }
+}
+
+impl DependencyGraph {
+    /// Build the graph for the given region, using `index` to find which files share data
+    pub fn generate<A: ArcLookup + ?Sized>(arc: &A, index: &ReverseIndex, region: Region) -> Result<Self, LookupError> {
+        let mut edges = Vec::new();
+
+        for (file_data_index, paths) in index.shared(region) {
+            edges.extend(paths.into_iter().map(|path| GraphEdge {
+                from: GraphNode::File(path),
+                to: GraphNode::FileData(file_data_index),
+                kind: GraphEdgeKind::UsesData,
+            }));
+        }
+
+        // A file belongs to the mass-loading group its data is loaded from, which isn't necessarily
+        // the group of the directory listing it
+        let mut members = BTreeMap::<usize, Vec<GraphNode>>::new();
+        for file_info in arc.get_file_infos() {
+            let info_to_data = arc.try_get_file_in_folder(file_info, region)?;
+            members.entry(info_to_data.folder_offset_index as usize).or_default().push(file_node(arc, file_info)?);
+        }
+
+        for (&group, files) in &members {
+            edges.extend(files.iter().map(|&file| GraphEdge { from: GraphNode::Group(group), to: file, kind: GraphEdgeKind::Contains }));
+        }
+
+        let folder_offsets = arc.get_folder_offsets();
+        let mut groups = BTreeSet::new();
+        for dir_info in arc.get_dir_infos() {
+            let from = GraphNode::Dir(dir_info.path.hash40());
+
+            let own_group = dir_info.path.index() as usize;
+            edges.push(GraphEdge { from, to: GraphNode::Group(own_group), kind: GraphEdgeKind::OwnGroup });
+
+            match arc.try_get_directory_dependency_ref(dir_info)? {
+                Some(RedirectionRef::Symlink(target)) => {
+                    edges.push(GraphEdge { from, to: GraphNode::Dir(target.path.hash40()), kind: GraphEdgeKind::Symlink });
+                }
+                Some(RedirectionRef::Shared(_)) => {
+                    // Nodes refer to groups by index, which the borrowed group doesn't carry. The
+                    // lookup has already checked that this entry exists.
+                    let group = folder_offsets[own_group].directory_index as usize;
+                    edges.push(GraphEdge { from, to: GraphNode::Group(group), kind: GraphEdgeKind::SharedGroup });
+                    groups.insert(group);
+                }
+                None => {}
+            }
+        }
+
+        // Shared file groups list the FileInfos of the files they redirect to, which load their
+        // data from elsewhere
+        for group in groups {
+            let folder_offset = checked(folder_offsets, group, TableKind::FolderOffsets)?;
+            for file_info in checked_range(arc.get_file_infos(), folder_offset.range(), TableKind::FileInfos)? {
+                edges.push(GraphEdge { from: GraphNode::Group(group), to: file_node(arc, file_info)?, kind: GraphEdgeKind::Contains });
+            }
+        }
+
+        Ok(Self { edges })
+    }
+
+    /// Every other path which uses the same file data as `path`, which would all change if the
+    /// data was replaced
+    pub fn sharing_data_with<Hash: Into<Hash40>>(&self, path: Hash) -> Vec<Hash40> {
+        let path = GraphNode::File(path.into());
+        let file_datas: Vec<_> = self.edges.iter()
+            .filter(|edge| edge.kind == GraphEdgeKind::UsesData && edge.from == path)
+            .map(|edge| edge.to)
+            .collect();
+
+        self.edges.iter()
+            .filter(|edge| edge.kind == GraphEdgeKind::UsesData && edge.from != path && file_datas.contains(&edge.to))
+            .filter_map(|edge| match edge.from {
+                GraphNode::File(hash) => Some(hash),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Every node which is part of an edge, without duplicates
+    pub fn nodes(&self) -> BTreeSet<GraphNode> {
+        self.edges.iter().flat_map(|edge| [edge.from, edge.to]).collect()
+    }
+
+    /// Export the graph in Graphviz's DOT format, naming paths with `labels` where possible
+    pub fn to_dot(&self, labels: &HashLabels) -> String {
+        let mut dot = String::from("digraph arc {\n");
+
+        for node in self.nodes() {
+            let shape = match node {
+                GraphNode::File(_) => "note",
+                GraphNode::Dir(_) => "folder",
+                GraphNode::Group(_) => "box3d",
+                GraphNode::FileData(_) => "cylinder",
+            };
+            let _ = writeln!(dot, "    {} [shape={}];", dot_quoted(&node_id(node, labels)), shape);
+        }
+
+        for edge in &self.edges {
+            let _ = writeln!(
+                dot,
+                "    {} -> {} [label={}];",
+                dot_quoted(&node_id(edge.from, labels)),
+                dot_quoted(&node_id(edge.to, labels)),
+                dot_quoted(edge.kind.name()),
+            );
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Export the graph as JSON, with a list of nodes and a list of edges between their ids
+    pub fn to_json(&self, labels: &HashLabels) -> String {
+        let nodes: Vec<_> = self.nodes()
+            .into_iter()
+            .map(|node| {
+                let kind = match node {
+                    GraphNode::File(_) => "file",
+                    GraphNode::Dir(_) => "dir",
+                    GraphNode::Group(_) => "group",
+                    GraphNode::FileData(_) => "file_data",
+                };
+                format!("{{\"id\":{},\"kind\":\"{}\"}}", json_quoted(&node_id(node, labels)), kind)
+            })
+            .collect();
+
+        let edges: Vec<_> = self.edges.iter()
+            .map(|edge| format!(
+                "{{\"from\":{},\"to\":{},\"kind\":\"{}\"}}",
+                json_quoted(&node_id(edge.from, labels)),
+                json_quoted(&node_id(edge.to, labels)),
+                edge.kind.name(),
+            ))
+            .collect();
+
+        format!("{{\"nodes\":[{}],\"edges\":[{}]}}", nodes.join(","), edges.join(","))
+    }
+}
+
+fn file_node<A: ArcLookup + ?Sized>(arc: &A, file_info: &FileInfo) -> Result<GraphNode, LookupError> {
+    let file_path = checked(arc.get_file_paths(), usize::from(file_info.file_path_index), TableKind::FilePaths)?;
+
+    Ok(GraphNode::File(file_path.path.hash40()))
+}
+
+/// A name for the node which is unique within the graph
+fn node_id(node: GraphNode, labels: &HashLabels) -> String {
+    let hash_name = |hash: Hash40| match hash.label(labels) {
+        Some(label) => label.to_owned(),
+        None => format!("0x{:010x}", hash.as_u64()),
+    };
+
+    match node {
+        GraphNode::File(hash) => hash_name(hash),
+        GraphNode::Dir(hash) => format!("{}/", hash_name(hash)),
+        GraphNode::Group(index) => format!("group:{}", index),
+        GraphNode::FileData(index) => format!("file_data:{}", index.0),
+    }
+}
+
+/// Quote a string for use as a DOT ID. Only quotes are escaped in DOT, so backslashes are
+/// escaped as well to keep them from escaping the quotes which follow them.
+fn dot_quoted(string: &str) -> String {
+    let mut quoted = String::with_capacity(string.len() + 2);
+    quoted.push('"');
+    for c in string.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Quote a string as a JSON string
+fn json_quoted(string: &str) -> String {
+    let mut quoted = String::with_capacity(string.len() + 2);
+    quoted.push('"');
+    for c in string.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c if c.is_control() => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash40;
+    use crate::test_arc::ArcBuilder;
+
+    #[test]
+    fn dependency_graph() {
+        let builder = ArcBuilder::sample();
+        let labels = builder.labels();
+        let arc = builder.open();
+        let graph = arc.dependency_graph(Region::UsEnglish).unwrap();
+
+        assert_eq!(
+            graph.sharing_data_with("fighter/mario/model/body/c00/leyes_eye_mario_l_col.nutexb"),
+            [hash40("fighter/luigi/model/body/c00/leyes_eye_mario_l_col.nutexb")]
+        );
+        assert!(graph.sharing_data_with("fighter/mario/model/body/c00/model.numdlb").is_empty());
+
+        assert!(graph.edges.contains(&GraphEdge {
+            from: GraphNode::Dir(hash40("fighter/mario/c00")),
+            to: GraphNode::Dir(hash40("fighter/mario/model/body/c00")),
+            kind: GraphEdgeKind::Symlink,
+        }));
+
+        let group = graph.edges.iter()
+            .find(|edge| edge.from == GraphNode::Dir(hash40("fighter/luigi/c00")) && edge.kind == GraphEdgeKind::SharedGroup)
+            .map(|edge| edge.to)
+            .unwrap();
+        let contained: Vec<_> = graph.edges.iter()
+            .filter(|edge| edge.from == group && edge.kind == GraphEdgeKind::Contains)
+            .map(|edge| edge.to)
+            .collect();
+        assert_eq!(contained, [
+            GraphNode::File(hash40("fighter/luigi/model/body/c00/model.numdlb")),
+            GraphNode::File(hash40("fighter/luigi/model/body/c00/leyes_eye_mario_l_col.nutexb")),
+        ]);
+
+        // Directories without a redirect still link to the group they load their files from
+        let c00 = arc.get_dir_info_from_hash("fighter/mario/model/body/c00").unwrap();
+        let own_group = GraphNode::Group(c00.path.index() as usize);
+        assert!(graph.edges.contains(&GraphEdge {
+            from: GraphNode::Dir(hash40("fighter/mario/model/body/c00")),
+            to: own_group,
+            kind: GraphEdgeKind::OwnGroup,
+        }));
+        let contained = |group| graph.edges.iter()
+            .filter(|edge| edge.from == group && edge.kind == GraphEdgeKind::Contains)
+            .map(|edge| edge.to)
+            .collect::<Vec<_>>();
+        assert_eq!(contained(own_group), [
+            GraphNode::File(hash40("fighter/mario/model/body/c00/model.numdlb")),
+            GraphNode::File(hash40("fighter/mario/model/body/c00/def_mario_001_col.nutexb")),
+        ]);
+
+        // Shared files belong to the group their data is loaded from, not their directory's group
+        let eyes = arc.get_file_info_from_hash(hash40("fighter/mario/model/body/c00/leyes_eye_mario_l_col.nutexb")).unwrap();
+        let shared_group = arc.get_file_in_folder(eyes, Region::UsEnglish).folder_offset_index as usize;
+        assert_ne!(GraphNode::Group(shared_group), own_group);
+        assert!(contained(GraphNode::Group(shared_group)).contains(&GraphNode::File(hash40("fighter/mario/model/body/c00/leyes_eye_mario_l_col.nutexb"))));
+
+        let dot = graph.to_dot(&labels);
+        assert!(dot.starts_with("digraph arc {\n"));
+        assert!(dot.contains("    \"fighter/mario/c00/\" -> \"fighter/mario/model/body/c00/\" [label=\"symlink\"];\n"));
+
+        let json = graph.to_json(&labels);
+        assert!(json.contains("{\"id\":\"fighter/luigi/c00/\",\"kind\":\"dir\"}"));
+        assert!(json.contains("{\"from\":\"fighter/mario/c00/\",\"to\":\"fighter/mario/model/body/c00/\",\"kind\":\"symlink\"}"));
+    }
+
+    #[test]
+    fn quoting() {
+        assert_eq!(dot_quoted("a\"b\\c\n"), "\"a\\\"b\\\\c\n\"");
+        assert_eq!(json_quoted("a\"b\\c\n\u{1}"), "\"a\\\"b\\\\c\\n\\u0001\"");
+    }
+}
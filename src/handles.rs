@@ -0,0 +1,230 @@
+use std::fmt;
+
+use crate::lookups::{checked, checked_range, FileMetadata};
+use crate::{
+    ArcLookup, DirInfo, DirectoryOffset, FileInfo, FilePath, Hash40, LookupError, LookupStep, RedirectionRef, Region,
+    TableKind,
+};
+
+/// A directory of an arc, borrowed along with the arc itself so it can be navigated without
+/// indexing into the tables by hand
+pub struct DirRef<'a, A: ?Sized> {
+    arc: &'a A,
+    dir_info: &'a DirInfo,
+}
+
+/// A file of an arc, borrowed along with the arc itself
+pub struct FileRef<'a, A: ?Sized> {
+    arc: &'a A,
+    file_info: &'a FileInfo,
+}
+
+/// Where a redirected directory gets its contents from
+pub enum DirRedirect<'a, A: ?Sized> {
+    Symlink(DirRef<'a, A>),
+    /// A shared file group, along with the files in it
+    Shared(&'a DirectoryOffset, Vec<FileRef<'a, A>>),
+}
+
+impl<'a, A: ArcLookup + ?Sized> DirRef<'a, A> {
+    pub fn new(arc: &'a A, dir_info: &'a DirInfo) -> Self {
+        Self { arc, dir_info }
+    }
+
+    pub fn from_hash<Hash: Into<Hash40>>(arc: &'a A, hash: Hash) -> Result<Self, LookupError> {
+        Ok(Self::new(arc, arc.get_dir_info_from_hash(hash)?))
+    }
+
+    pub fn dir_info(&self) -> &'a DirInfo {
+        self.dir_info
+    }
+
+    /// The hash of the directory's full path
+    pub fn hash(&self) -> Hash40 {
+        self.dir_info.path.hash40()
+    }
+
+    /// The hash of the last component of the directory's path
+    pub fn name(&self) -> Hash40 {
+        self.dir_info.name
+    }
+
+    /// The directory containing this one, or `None` for top-level directories
+    pub fn parent(&self) -> Result<Option<Self>, LookupError> {
+        optional(Self::from_hash(self.arc, self.dir_info.parent))
+    }
+
+    /// The subdirectories of this directory
+    pub fn children(&self) -> Result<Vec<Self>, LookupError> {
+        let dir_infos = self.arc.get_dir_infos();
+
+        checked_range(self.arc.get_folder_child_hashes(), self.dir_info.children_range(), TableKind::FolderChildHashes)?
+            .iter()
+            .map(|child| Ok(Self::new(self.arc, checked(dir_infos, child.index() as usize, TableKind::DirInfos)?)))
+            .collect()
+    }
+
+    /// The files stored in this directory, not including those of its redirection
+    pub fn files(&self) -> Result<Vec<FileRef<'a, A>>, LookupError> {
+        let file_infos = checked_range(self.arc.get_file_infos(), self.dir_info.file_info_range(), TableKind::FileInfos)?;
+
+        Ok(file_refs(self.arc, file_infos))
+    }
+
+    /// The directory or shared file group this directory redirects to, if any
+    pub fn redirect(&self) -> Result<Option<DirRedirect<'a, A>>, LookupError> {
+        let redirect = match self.arc.try_get_directory_dependency_ref(self.dir_info)? {
+            Some(RedirectionRef::Symlink(dir_info)) => DirRedirect::Symlink(Self::new(self.arc, dir_info)),
+            Some(RedirectionRef::Shared(group)) => {
+                let file_infos = checked_range(self.arc.get_file_infos(), group.range(), TableKind::FileInfos)?;
+                DirRedirect::Shared(group, file_refs(self.arc, file_infos))
+            }
+            None => return Ok(None),
+        };
+
+        Ok(Some(redirect))
+    }
+}
+
+impl<'a, A: ArcLookup + ?Sized> FileRef<'a, A> {
+    pub fn new(arc: &'a A, file_info: &'a FileInfo) -> Self {
+        Self { arc, file_info }
+    }
+
+    pub fn from_hash<Hash: Into<Hash40>>(arc: &'a A, hash: Hash) -> Result<Self, LookupError> {
+        Ok(Self::new(arc, arc.get_file_info_from_hash(hash.into())?))
+    }
+
+    pub fn file_info(&self) -> &'a FileInfo {
+        self.file_info
+    }
+
+    /// The hashes of the file's path, parent directory, file name and extension
+    pub fn file_path(&self) -> Result<&'a FilePath, LookupError> {
+        checked(self.arc.get_file_paths(), usize::from(self.file_info.file_path_index), TableKind::FilePaths)
+    }
+
+    /// The hash of the file's full path
+    pub fn hash(&self) -> Result<Hash40, LookupError> {
+        Ok(self.file_path()?.path.hash40())
+    }
+
+    /// The directory containing the file, if it has a [`DirInfo`]
+    pub fn parent(&self) -> Result<Option<DirRef<'a, A>>, LookupError> {
+        optional(DirRef::from_hash(self.arc, self.file_path()?.parent.hash40()))
+    }
+
+    pub fn metadata(&self, region: Region) -> Result<FileMetadata, LookupError> {
+        self.arc.get_file_metadata(self.hash()?, region)
+    }
+
+    /// Read and decompress the file
+    pub fn read(&self, region: Region) -> Result<Vec<u8>, LookupError> {
+        self.arc.get_file_contents(self.hash()?, region)
+    }
+}
+
+fn file_refs<'a, A: ArcLookup + ?Sized>(arc: &'a A, file_infos: &'a [FileInfo]) -> Vec<FileRef<'a, A>> {
+    file_infos.iter().map(|file_info| FileRef::new(arc, file_info)).collect()
+}
+
+/// Treat a directory which doesn't exist as `None`, while still returning other errors
+fn optional<T>(result: Result<T, LookupError>) -> Result<Option<T>, LookupError> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(LookupError::Missing { step: LookupStep::DirInfo, .. }) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+// Derives would require `A: Clone`/`A: Debug`, but only a reference to the arc is held
+
+impl<A: ?Sized> Clone for DirRef<'_, A> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<A: ?Sized> Copy for DirRef<'_, A> {}
+
+impl<A: ?Sized> Clone for FileRef<'_, A> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<A: ?Sized> Copy for FileRef<'_, A> {}
+
+impl<A: ?Sized> fmt::Debug for DirRef<'_, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("DirRef").field(self.dir_info).finish()
+    }
+}
+
+impl<A: ?Sized> fmt::Debug for FileRef<'_, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("FileRef").field(self.file_info).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_arc::ArcBuilder;
+    use crate::{hash40, ArcFileRef};
+
+    fn hashes<A: ArcLookup + ?Sized>(files: Vec<FileRef<A>>) -> Vec<Hash40> {
+        files.iter().map(|file| file.hash().unwrap()).collect()
+    }
+
+    #[test]
+    fn navigate() {
+        let data = ArcBuilder::sample().build();
+        let arc = ArcFileRef::new(&data).unwrap();
+
+        let mario = arc.get_dir_ref("fighter/mario").unwrap();
+        assert_eq!(mario.name(), hash40("mario"));
+        assert_eq!(mario.parent().unwrap().unwrap().hash(), hash40("fighter"));
+        assert!(mario.parent().unwrap().unwrap().parent().unwrap().is_none());
+
+        let children: Vec<_> = mario.children().unwrap().iter().map(DirRef::hash).collect();
+        assert_eq!(children, [hash40("fighter/mario/c00"), hash40("fighter/mario/model"), hash40("fighter/mario/motion")]);
+        assert!(mario.files().unwrap().is_empty());
+
+        let file = arc.get_file_ref("fighter/mario/model/body/c00/def_mario_001_col.nutexb").unwrap();
+        assert_eq!(file.parent().unwrap().unwrap().hash(), hash40("fighter/mario/model/body/c00"));
+        assert_eq!(file.metadata(Region::None).unwrap().decomp_size, 0x300);
+        assert_eq!(file.read(Region::None).unwrap(), [0x55; 0x300]);
+
+        let file = arc.get_file_ref("ui/message/msg_name.msbt").unwrap();
+        assert_eq!(file.read(Region::Korean).unwrap(), b"name Korean");
+    }
+
+    #[test]
+    fn redirects() {
+        let data = ArcBuilder::sample().build();
+        let arc = ArcFileRef::new(&data).unwrap();
+
+        assert!(arc.get_dir_ref("fighter/mario/model/body/c00").unwrap().redirect().unwrap().is_none());
+
+        match arc.get_dir_ref("fighter/mario/c00").unwrap().redirect().unwrap() {
+            Some(DirRedirect::Symlink(target)) => {
+                assert_eq!(target.hash(), hash40("fighter/mario/model/body/c00"));
+                assert_eq!(hashes(target.files().unwrap()), [
+                    hash40("fighter/mario/model/body/c00/model.numdlb"),
+                    hash40("fighter/mario/model/body/c00/def_mario_001_col.nutexb"),
+                    hash40("fighter/mario/model/body/c00/leyes_eye_mario_l_col.nutexb"),
+                ]);
+            }
+            _ => panic!("expected a symlink"),
+        }
+
+        match arc.get_dir_ref("fighter/luigi/c00").unwrap().redirect().unwrap() {
+            Some(DirRedirect::Shared(_, files)) => assert_eq!(hashes(files), [
+                hash40("fighter/luigi/model/body/c00/model.numdlb"),
+                hash40("fighter/luigi/model/body/c00/leyes_eye_mario_l_col.nutexb"),
+            ]),
+            _ => panic!("expected a shared group"),
+        }
+    }
+}
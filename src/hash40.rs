@@ -11,6 +11,7 @@ impl Hash40 {
         self.0
     }
 
+    #[allow(clippy::len_without_is_empty)]
     pub fn len(self) -> u8 {
         (self.0 >> 32) as u8
     }
@@ -185,7 +186,7 @@ pub mod serde {
         {
             if v.starts_with("0x") {
                 Ok(u64::from_str_radix(v.trim_start_matches("0x"), 16)
-                    .map_or_else(|_| Hash40::from(v), |val| Hash40(val)))
+                    .map_or_else(|_| Hash40::from(v), Hash40))
             } else {
                 Ok(Hash40::from(v))
             }
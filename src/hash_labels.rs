@@ -1,12 +1,16 @@
 use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
+use std::sync::Arc;
 
 use crate::{hash40, Hash40};
 use parking_lot::RwLock;
 
+/// A table of the strings hashes were generated from. Cloning is cheap, as clones share the
+/// table until one of them is modified.
+#[derive(Clone, Default)]
 pub struct HashLabels {
-    pub(crate) labels: HashMap<Hash40, String>,
+    pub(crate) labels: Arc<HashMap<Hash40, String>>,
 }
 
 impl HashLabels {
@@ -20,17 +24,18 @@ impl HashLabels {
 
     pub fn from_string(text: &str) -> Self {
         HashLabels {
-            labels: text
-                .lines()
-                .map(|line| (hash40(&line), line.to_owned()))
-                .collect(),
+            labels: Arc::new(
+                text.lines()
+                    .map(|line| (hash40(line), line.to_owned()))
+                    .collect(),
+            ),
         }
     }
 
     pub(crate) fn add_label<S: Into<String>>(&mut self, label: S) -> Hash40 {
         let label = label.into();
         let hash = hash40(&label);
-        self.labels.insert(hash, label);
+        Arc::make_mut(&mut self.labels).insert(hash, label);
 
         hash
     }
@@ -48,7 +53,7 @@ impl Hash40 {
     }
 
     pub fn global_label(self) -> Option<String> {
-        GLOBAL_LABELS.read().labels.get(&self).map(Clone::clone)
+        GLOBAL_LABELS.read().labels.get(&self).cloned()
     }
 
     //pub fn global_label<'a>(self) -> MappedRwLockReadGuard<'a, Option<&'a str>> {
@@ -69,6 +74,7 @@ impl Hash40 {
 }
 
 lazy_static::lazy_static! {
+    /// Shared with every [`ArcFile`](crate::ArcFile) opened while they are set, rather than copied
     pub static ref GLOBAL_LABELS: RwLock<HashLabels> = RwLock::new(HashLabels::new());
 }
 
@@ -79,15 +85,16 @@ mod tests {
     #[cfg(feature = "search")]
     #[test]
     fn search_labels() {
-        let labels = HashLabels::from_file("hash_labels.txt").unwrap();
-        let before = std::time::Instant::now();
+        let labels = HashLabels::from_string("fighter/mario\nfighter/luigi\nui/param");
         let matches = labels.get_ordered_matches("mar");
+
+        assert_eq!(matches, [hash40("fighter/mario")]);
     }
 
     #[test]
     fn from_string_line_feed() {
         let text = "a\nbc\ndef\n";
-        let labels = HashLabels::from_string(&text);
+        let labels = HashLabels::from_string(text);
 
         let hash_a = hash40("a");
         let hash_bc = hash40("bc");
@@ -102,7 +109,7 @@ mod tests {
     fn from_string_carriage_return_line_feed() {
         // Ensure the hash label file still works when edited on Windows.
         let text = "a\r\nbc\r\ndef\r\n";
-        let labels = HashLabels::from_string(&text);
+        let labels = HashLabels::from_string(text);
 
         let hash_a = hash40("a");
         let hash_bc = hash40("bc");
@@ -112,4 +119,15 @@ mod tests {
         assert_eq!("bc", hash_bc.label(&labels).unwrap());
         assert_eq!("def", hash_def.label(&labels).unwrap());
     }
+
+    #[test]
+    fn clones_share_until_modified() {
+        let labels = HashLabels::from_string("a\nbc");
+        let mut copy = labels.clone();
+        assert!(Arc::ptr_eq(&labels.labels, &copy.labels));
+
+        copy.add_label("def");
+        assert_eq!(hash40("def").label(&copy), Some("def"));
+        assert_eq!(hash40("def").label(&labels), None);
+    }
 }
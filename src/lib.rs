@@ -1,24 +1,24 @@
 //! A library for working with the data.arc file from Smash Ultimate.
 //!
-//! ```rust
-//! use smash_arc::{ArcFile, ArcLookup, FileNode, Hash40, Region};
+//! ```rust,no_run
+//! use smash_arc::{ArcFile, ArcLookup, FileNode, HashLabels, Region};
 //!
 //! // Load the hashes needed to list directories (file format restriction)
-//! Hash40::set_global_labels_file("hash_labels.txt").unwrap();
+//! let labels = HashLabels::from_file("hash_labels.txt").unwrap();
 //!
 //! // Parse the arc from a file
-//! let arc = ArcFile::open("data.arc").unwrap();
+//! let arc = ArcFile::open_with_labels("data.arc", labels).unwrap();
 //!
 //! // loop over every file in the root
 //! for node in arc.get_dir_listing("/").unwrap() {
 //!     match node {
 //!         FileNode::Dir(dir) => {
 //!             // print out name of directory
-//!             println!("directory: {}", dir.global_label().unwrap());
+//!             println!("directory: {}", arc.label(*dir).unwrap());
 //!         }
 //!         FileNode::File(file) => {
 //!             // extract file
-//!             let path = file.global_label().unwrap();
+//!             let path = arc.label(*file).unwrap();
 //!             std::fs::write(path, arc.get_file_contents(file, Region::UsEnglish).unwrap()).unwrap();
 //!         }
 //!     }
@@ -31,9 +31,11 @@
 //! * `dir-listing` (enabled by default) = List directories 
 //! * `global-hashes` (enabled by default) = Enable a global table for cracking hashes
 //! * `smash-runtime` = Enables features for running under the context of Smash Ultimate itself
-//! (enable Aarch64 crc32 hardware acceleration, enable parsing the Arc from the game's memory
-//! layout)
+//!   (enable Aarch64 crc32 hardware acceleration, enable parsing the Arc from the game's memory
+//!   layout)
 //! * `search` = Enable functionality to fuzzy search [`ArcFile`]s
+//! * `extract` = Enable extracting directories of an [`ArcFile`] to disk in parallel
+//! * `fuzzing` = Expose the entry points used by the fuzz targets in the `fuzz` directory
 //! 
 //! * ZSTD backends
 //!   * `libzstd` - Recommended for use on platforms it builds for
@@ -43,7 +45,19 @@
 mod hash40;
 mod region;
 mod lookups;
+mod batch;
+mod progress;
+mod verify;
+mod validation;
+mod walkdir;
+mod handles;
+mod files;
+mod reverse_index;
+mod graph;
+mod resolve;
+mod error;
 mod filesystem;
+mod file_reader;
 mod hash_labels;
 mod zstd_backend;
 mod ffi_bindings;
@@ -52,16 +66,44 @@ mod table_indices;
 #[cfg(feature = "search")]
 mod search;
 
+#[cfg(feature = "extract")]
+mod extract;
+
 #[cfg(feature = "smash-runtime")]
 mod loaded_arc;
 mod arc_file;
+mod arc_file_ref;
+
+#[cfg(feature = "dir-listing")]
+mod dir_listing;
+
+#[cfg(test)]
+mod test_arc;
+
+#[cfg(any(feature = "fuzzing", test))]
+pub mod fuzz;
 
 #[cfg(feature = "smash-runtime")]
 pub use loaded_arc::*;
 
+#[cfg(feature = "extract")]
+pub use extract::ExtractReport;
+
 pub use arc_file::*;
+pub use arc_file_ref::ArcFileRef;
 pub use filesystem::*;
-pub use lookups::{ArcLookup, SearchLookup, LookupError};
+pub use file_reader::{FileReader, FileSeeker};
+pub use progress::{Progress, ProgressHook};
+pub use verify::{VerifyReport, VerifyIssue, VerifyEntry, VerifyIssueKind};
+pub use validation::{TableKind, ValidationError};
+pub use walkdir::{WalkdirIter, WalkdirEntry, WalkdirDirectoryType};
+pub use handles::{DirRef, FileRef, DirRedirect};
+pub use files::FilesIter;
+pub use reverse_index::ReverseIndex;
+pub use graph::{DependencyGraph, GraphNode, GraphEdge, GraphEdgeKind};
+pub use resolve::{ResolvedFile, ResolvedDir};
+pub use lookups::{ArcLookup, SearchLookup, LookupError, LookupStep, FileMetadata, StreamDir, stream_dir_hash, MAX_GROUP_SIZE};
+pub use error::Error;
 pub use hash40::{hash40, Hash40};
 pub use hash_labels::{GLOBAL_LABELS, HashLabels};
 pub use table_indices::*;
@@ -82,9 +124,18 @@ pub use region::Region;
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_arc::ArcBuilder;
 
     #[test]
     fn test_parse() {
-        let arc = ArcFile::open("/home/jam/re/ult/900/data.arc").unwrap();
+        let path = std::env::temp_dir().join(format!("smash-arc-test-parse-{}.arc", std::process::id()));
+        std::fs::write(&path, ArcBuilder::sample().build()).unwrap();
+
+        let arc = ArcFile::open(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let arc = arc.unwrap();
+        assert_eq!(arc.file_system.file_paths.len(), 7);
+        assert_eq!(arc.file_system.stream_entries.len(), 3);
     }
 }
@@ -2,7 +2,7 @@ use std::{fs::File, io::BufReader};
 
 use binrw::{BinReaderExt, BinResult};
 
-pub use crate::filesystem::*;
+use crate::filesystem::*;
 use crate::ArcFile;
 use crate::SeekRead;
 
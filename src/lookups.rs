@@ -1,5 +1,6 @@
 use crate::*;
 use std::ops::Range;
+use std::collections::{HashMap, HashSet};
 use std::io::{self, SeekFrom, Read, Seek};
 
 use region::Region;
@@ -13,11 +14,124 @@ pub enum LookupError {
     #[error("unsupported compression type, only zstd is supported")]
     UnsupportedCompression,
 
-    #[error("the requested resource could not be found")]
-    Missing,
+    #[error("{:#x} could not be found in the {step}", .hash.as_u64())]
+    Missing {
+        hash: Hash40,
+        /// The region being looked up, for lookups which depend on the region
+        region: Option<Region>,
+        step: LookupStep,
+    },
+
+    #[error("the file is compressed and cannot be seeked")]
+    NotSeekable,
+
+    #[error("failed to write the extracted file")]
+    FileWrite(std::io::Error),
+
+    #[error("the operation was cancelled")]
+    Cancelled,
+
+    #[error("the buffer is too small to hold the file ({needed} bytes needed, {provided} provided)")]
+    BufferTooSmall { needed: u64, provided: usize },
+
+    #[error("index {index} is out of bounds of the {table}")]
+    CorruptIndex { table: TableKind, index: usize },
+
+    #[error("{:#x} redirects in a loop, coming back around to {:#x}", .hash.as_u64(), .redirect.as_u64())]
+    RedirectLoop { hash: Hash40, redirect: Hash40 },
+
+    #[error("mass-loading group {index} is {size:#x} bytes, more than the {:#x} which will be read at once", MAX_GROUP_SIZE)]
+    GroupTooLarge { index: u32, size: u64 },
+
+    #[error("{:#x} is stored outside of the bounds of its mass-loading group", .hash.as_u64())]
+    OutsideGroup { hash: Hash40 },
+}
+
+/// The largest mass-loading group which [`read_directory_group`](ArcLookup::read_directory_group)
+/// will read. Larger sizes in a [`DirectoryOffset`] are treated as corruption rather than allocated.
+pub const MAX_GROUP_SIZE: u64 = 0x1000_0000;
+
+impl LookupError {
+    pub(crate) fn missing(hash: Hash40, step: LookupStep) -> Self {
+        LookupError::Missing { hash, region: None, step }
+    }
+
+    /// Attach the region being looked up to a [`LookupError::Missing`]
+    pub(crate) fn in_region(self, region: Region) -> Self {
+        match self {
+            LookupError::Missing { hash, region: None, step } => LookupError::Missing { hash, region: Some(region), step },
+            err => err,
+        }
+    }
+}
+
+/// The step of a lookup at which the requested resource could not be found
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LookupStep {
+    /// The hash isn't in its bucket of the file hashes
+    FileBucket,
+    /// The hash isn't one of the directory hashes
+    DirInfo,
+    /// The hash isn't one of the stream entries
+    StreamEntry,
+    /// The stream directory isn't one of the quick dirs
+    QuickDir,
+    /// The directory has no listing
+    DirListing,
+    /// The hash isn't one of the folders of the search section
+    SearchFolder,
+    /// The hash isn't one of the paths of the search section
+    SearchPath,
+    /// The folder of the search section has no more children
+    SearchChild,
+}
+
+impl std::fmt::Display for LookupStep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let step = match self {
+            LookupStep::FileBucket => "file hash buckets",
+            LookupStep::DirInfo => "directory hashes",
+            LookupStep::StreamEntry => "stream entries",
+            LookupStep::QuickDir => "stream quick dirs",
+            LookupStep::DirListing => "directory listing",
+            LookupStep::SearchFolder => "search folders",
+            LookupStep::SearchPath => "search paths",
+            LookupStep::SearchChild => "search folder children",
+        };
+
+        f.write_str(step)
+    }
+}
+
+/// Index into a table, returning [`LookupError::CorruptIndex`] if the index is out of bounds
+pub(crate) fn checked<T>(table: &[T], index: usize, kind: TableKind) -> Result<&T, LookupError> {
+    table.get(index).ok_or(LookupError::CorruptIndex { table: kind, index })
+}
+
+pub(crate) fn checked_mut<T>(table: &mut [T], index: usize, kind: TableKind) -> Result<&mut T, LookupError> {
+    table.get_mut(index).ok_or(LookupError::CorruptIndex { table: kind, index })
+}
+
+/// Every directory which isn't the child of another directory
+pub(crate) fn top_level_dirs<A: ArcLookup + ?Sized>(arc: &A) -> impl Iterator<Item = &DirInfo> {
+    let child_dirs: HashSet<Hash40> = arc.get_folder_child_hashes()
+        .iter()
+        .map(HashToIndex::hash40)
+        .collect();
+
+    arc.get_dir_infos()
+        .iter()
+        .filter(move |dir_info| !child_dirs.contains(&dir_info.path.hash40()))
+}
+
+/// Slice a table, returning [`LookupError::CorruptIndex`] if the range is out of bounds
+pub(crate) fn checked_range<T>(table: &[T], range: Range<usize>, kind: TableKind) -> Result<&[T], LookupError> {
+    let index = range.start;
+    table.get(range).ok_or(LookupError::CorruptIndex { table: kind, index })
 }
 
 mod arc_file;
+mod arc_file_ref;
 #[cfg(feature = "smash-runtime")]
 mod loaded_arc;
 
@@ -39,6 +153,9 @@ pub trait ArcLookup {
     fn get_file_datas(&self) -> &[FileData];
     fn get_folder_offsets(&self) -> &[DirectoryOffset];
 
+    /// The children of every directory, see [`DirInfo::children_range`]
+    fn get_folder_child_hashes(&self) -> &[HashToIndex];
+
     fn get_stream_entries(&self) -> &[StreamEntry];
     fn get_stream_file_indices(&self) -> &[u32];
     fn get_stream_datas(&self) -> &[StreamData];
@@ -62,20 +179,108 @@ pub trait ArcLookup {
 
         self.get_nonstream_file_contents(hash, region)
             .or_else(|err| match err {
-                LookupError::Missing => self.get_stream_file_contents(hash),
+                LookupError::Missing { step: LookupStep::FileBucket, .. } => self.get_stream_file_contents(hash),
                 err => Err(err),
             })
     }
 
+    /// Open a file for reading, decompressing it as it is read rather than all at once.
+    ///
+    /// **Note:** for [`ArcFile`] the returned reader holds the lock on the underlying reader
+    /// until it is dropped, so other reads from the same arc will block until then.
+    fn open_file<Hash: Into<Hash40>>(&self, hash: Hash, region: Region) -> Result<FileReader<'_>, LookupError> {
+        let metadata = self.get_file_metadata(hash, region)?;
+
+        FileReader::new(self.get_file_reader(), &metadata)
+    }
+
+    /// Open an uncompressed or stream file for reading with support for seeking. Returns
+    /// [`LookupError::NotSeekable`] for compressed files.
+    ///
+    /// **Note:** for [`ArcFile`] the returned reader holds the lock on the underlying reader
+    /// until it is dropped, so other reads from the same arc will block until then.
+    fn open_file_seekable<Hash: Into<Hash40>>(&self, hash: Hash, region: Region) -> Result<FileSeeker<'_>, LookupError> {
+        let metadata = self.get_file_metadata(hash, region)?;
+
+        FileSeeker::new(self.get_file_reader(), &metadata)
+    }
+
+    /// Read `len` bytes starting at `offset` within a file, stopping early at the end of the
+    /// file. Uncompressed and stream files are seeked directly, while compressed files are only
+    /// decoded up to the end of the range.
+    fn read_file_range<Hash: Into<Hash40>>(&self, hash: Hash, region: Region, offset: u64, len: u64) -> Result<Vec<u8>, LookupError> {
+        let metadata = self.get_file_metadata(hash, region)?;
+
+        let offset = offset.min(metadata.decomp_size);
+        let end = offset.saturating_add(len).min(metadata.decomp_size);
+        let len = end - offset;
+
+        // Nothing to read, so don't seek or decompress past the end of the file
+        if len == 0 {
+            return Ok(Vec::new())
+        }
+
+        let mut data = Vec::with_capacity(len as usize);
+        if metadata.is_compressed {
+            let mut reader = FileReader::new(self.get_file_reader(), &metadata)?;
+            io::copy(&mut Read::take(&mut reader, offset), &mut io::sink())?;
+            reader.take(len).read_to_end(&mut data)?;
+        } else {
+            let mut reader = FileSeeker::new(self.get_file_reader(), &metadata)?;
+            reader.seek(SeekFrom::Start(offset))?;
+            reader.take(len).read_to_end(&mut data)?;
+        }
+
+        if data.len() as u64 == len {
+            Ok(data)
+        } else {
+            Err(LookupError::FileRead(io::Error::new(io::ErrorKind::UnexpectedEof, "Failed to read data")))
+        }
+    }
+
+    /// Decompress a file into a caller-provided buffer, returning the size of the file. Returns
+    /// [`LookupError::BufferTooSmall`] if the buffer can't hold the decompressed file.
+    fn read_file_into<Hash: Into<Hash40>>(&self, hash: Hash, region: Region, buf: &mut [u8]) -> Result<usize, LookupError> {
+        let metadata = self.get_file_metadata(hash, region)?;
+
+        if metadata.decomp_size > buf.len() as u64 {
+            return Err(LookupError::BufferTooSmall {
+                needed: metadata.decomp_size,
+                provided: buf.len(),
+            })
+        }
+
+        let buf = &mut buf[..metadata.decomp_size as usize];
+        FileReader::new(self.get_file_reader(), &metadata)?.read_exact(buf)?;
+
+        Ok(buf.len())
+    }
+
+    /// Decompress a file into a reusable buffer, replacing its previous contents. The buffer is
+    /// only reallocated if its capacity is smaller than the decompressed file.
+    fn read_file_into_vec<Hash: Into<Hash40>>(&self, hash: Hash, region: Region, buf: &mut Vec<u8>) -> Result<(), LookupError> {
+        let metadata = self.get_file_metadata(hash, region)?;
+
+        buf.clear();
+        buf.reserve(metadata.decomp_size as usize);
+
+        let reader = FileReader::new(self.get_file_reader(), &metadata)?;
+        if reader.take(metadata.decomp_size).read_to_end(buf)? as u64 == metadata.decomp_size {
+            Ok(())
+        } else {
+            Err(LookupError::FileRead(io::Error::new(io::ErrorKind::UnexpectedEof, "Failed to read data")))
+        }
+    }
+
     fn get_dir_info_from_hash<Hash: Into<Hash40>>(&self, hash: Hash) -> Result<&DirInfo, LookupError> {
         fn inner<Arc: ArcLookup + ?Sized>(arc: &Arc, hash: Hash40) -> Result<&DirInfo, LookupError> {
             let dir_hash_to_info_index = arc.get_dir_hash_to_info_index();
 
             let index = dir_hash_to_info_index.binary_search_by_key(&hash, |dir| dir.hash40())
                 .map(|index| dir_hash_to_info_index[index].index() as usize)
-                .map_err(|_| LookupError::Missing)?;
+                .map_err(|_| LookupError::missing(hash, LookupStep::DirInfo))?;
 
-            Ok(&arc.get_dir_infos()[index])
+            checked(arc.get_dir_infos(), index, TableKind::DirInfos)
         }
 
         inner(self, hash.into())
@@ -87,9 +292,11 @@ pub trait ArcLookup {
 
             let index = dir_hash_to_info_index.binary_search_by_key(&hash, |dir| dir.hash40())
                 .map(|index| dir_hash_to_info_index[index].index() as usize)
-                .map_err(|_| LookupError::Missing)?;
+                .map_err(|_| LookupError::missing(hash, LookupStep::DirInfo))?;
 
-            Ok(&mut arc.get_dir_infos_mut()[index])
+            arc.get_dir_infos_mut()
+                .get_mut(index)
+                .ok_or(LookupError::CorruptIndex { table: TableKind::DirInfos, index })
         }
 
         inner(self, hash.into())
@@ -97,27 +304,35 @@ pub trait ArcLookup {
 
     fn get_nonstream_file_contents<Hash: Into<Hash40>>(&self, hash: Hash, region: Region) -> Result<Vec<u8>, LookupError> {
         fn inner<Arc: ArcLookup + ?Sized>(arc: &Arc, hash: Hash40, region: Region) -> Result<Vec<u8>, LookupError> {
-            let file_info = arc.get_file_info_from_hash(hash)?;
-            let folder_offset = arc.get_folder_offset(file_info, region);
-            let file_data = arc.get_file_data(file_info, region);
+            let file_info = arc.get_file_info_from_hash(hash).map_err(|err| err.in_region(region))?;
+            let folder_offset = arc.try_get_folder_offset(file_info, region)?;
+            let file_data = arc.try_get_file_data(file_info, region)?;
 
-            arc.read_file_data(&file_data, folder_offset)
+            arc.read_file_data(file_data, folder_offset)
         }
 
         inner(self, hash.into(), region)
     }
 
+    /// Look up a stream file's data by binary searching the stream hashes, which are sorted in
+    /// the same way as the file buckets
     fn get_stream_data(&self, hash: Hash40) -> Result<&StreamData, LookupError> {
-        let stream_entries = self.get_stream_entries();
+        let hash_to_entries = self.get_stream_hash_to_entries();
 
-        let index = stream_entries.iter()
-            .find(|entry| entry.hash40() == hash)
-            .map(|entry| entry.index() as usize)
-            .ok_or(LookupError::Missing)?;
-        
-        let index = self.get_stream_file_indices()[index] as usize;
-        
-        Ok(&self.get_stream_datas()[index])
+        let index = hash_to_entries.binary_search_by_key(&hash, |entry| entry.hash40())
+            .map(|index| hash_to_entries[index].index() as usize)
+            .map_err(|_| LookupError::missing(hash, LookupStep::StreamEntry))?;
+
+        // A corrupt index could point at another stream's entry, which would quietly return the
+        // wrong data
+        let stream_entry = checked(self.get_stream_entries(), index, TableKind::StreamEntries)?;
+        if stream_entry.hash40() != hash {
+            return Err(LookupError::missing(hash, LookupStep::StreamEntry))
+        }
+
+        let index = *checked(self.get_stream_file_indices(), stream_entry.index() as usize, TableKind::StreamFileIndices)? as usize;
+
+        checked(self.get_stream_datas(), index, TableKind::StreamDatas)
     }
 
     fn get_stream_file_contents<Hash: Into<Hash40>>(&self, hash: Hash) -> Result<Vec<u8>, LookupError> {
@@ -136,7 +351,7 @@ pub trait ArcLookup {
         reader.seek(SeekFrom::Start(offset))?;
         
         let mut data = Vec::with_capacity(file_data.size as usize);
-        let mut reader = Read::take(&mut reader, file_data.size as u64);
+        let mut reader = Read::take(&mut reader, file_data.size);
         
         if reader.read_to_end(&mut data)? as u64 == file_data.size {
             Ok(data)
@@ -151,10 +366,10 @@ pub trait ArcLookup {
         if metadata.is_shared {
             let hash_to_paths = self.get_file_hash_to_path_index();
 
-            let file_data_index = self.get_file_in_folder(
+            let file_data_index = self.try_get_file_in_folder(
                 self.get_file_info_from_hash(hash)?,
                 region
-            ).file_data_index;
+            )?.file_data_index;
 
             Ok(
                 hash_to_paths
@@ -162,7 +377,7 @@ pub trait ArcLookup {
                     .filter_map(|hash_to_path| {
                         let hash = hash_to_path.hash40();
                         let file_info = self.get_file_info_from_hash(hash).ok()?;
-                        let file_in_folder = self.get_file_in_folder(file_info, region);
+                        let file_in_folder = self.try_get_file_in_folder(file_info, region).ok()?;
                         let is_same_fd_index = file_in_folder.file_data_index == file_data_index;
                         if is_same_fd_index {
                             Some(hash)
@@ -178,112 +393,302 @@ pub trait ArcLookup {
     }
 
     fn get_bucket_for_hash(&self, hash: Hash40) -> &[HashToIndex] {
+        self.try_get_bucket_for_hash(hash).unwrap()
+    }
+
+    /// Fallible version of [`get_bucket_for_hash`](ArcLookup::get_bucket_for_hash). An arc with
+    /// no buckets is treated as having no files.
+    fn try_get_bucket_for_hash(&self, hash: Hash40) -> Result<&[HashToIndex], LookupError> {
         let file_info_buckets = self.get_file_info_buckets();
+        if file_info_buckets.is_empty() {
+            return Ok(&[])
+        }
+
         let bucket_index = (hash.as_u64() % (file_info_buckets.len() as u64)) as usize;
-        let bucket = &file_info_buckets[bucket_index];
+        let range = file_info_buckets[bucket_index].range();
         
-        &self.get_file_hash_to_path_index()[bucket.range()]
+        self.get_file_hash_to_path_index()
+            .get(range.clone())
+            .ok_or(LookupError::CorruptIndex { table: TableKind::FileHashToPathIndex, index: range.start })
     }
 
     fn get_file_path_index_from_hash(&self, hash: Hash40) -> Result<FilePathIdx, LookupError> {
-        let bucket = self.get_bucket_for_hash(hash);
+        let bucket = self.try_get_bucket_for_hash(hash)?;
         
         let index_in_bucket = bucket.binary_search_by_key(&hash, |group| group.hash40())
-            .map_err(|_| LookupError::Missing)?;
+            .map_err(|_| LookupError::missing(hash, LookupStep::FileBucket))?;
 
         Ok(FilePathIdx(bucket[index_in_bucket].index()))
     }
 
     fn get_file_info_from_hash(&self, hash: Hash40) -> Result<&FileInfo, LookupError> {
         let path_index = self.get_file_path_index_from_hash(hash)?;
-        let file_info = self.get_file_info_from_path_index(path_index);
-        
-        Ok(file_info)
+
+        self.try_get_file_info_from_path_index(path_index)
     }
 
+    /// List the stream entries of a quick dir, given its path such as `"stream:/sound/bgm"` or
+    /// just its name such as `"bgm"`. See [`stream_dir_hash`] for how the path is resolved.
     fn get_stream_listing(&self, dir: &str) -> Result<&[StreamEntry], LookupError> {
-        let hash = match dir {
-            "bgm" | "smashappeal" | "movie" => crate::hash40::hash40(dir),
-            dir if dir.starts_with("stream:/sound") => crate::hash40::hash40(&dir[14..]),
-            "stream:/movie" => crate::hash40::hash40("movie"),
-            _ => return Err(LookupError::Missing)
-        };
+        let hash = stream_dir_hash(dir);
 
         self.get_quick_dirs()
             .iter()
             .find(|dir| dir.hash40() == hash)
-            .map(|dir| &self.get_stream_entries()[dir.range()])
-            .ok_or(LookupError::Missing)
+            .ok_or(LookupError::missing(hash, LookupStep::QuickDir))
+            .and_then(|dir| self.get_quick_dir_entries(dir))
+    }
+
+    fn get_quick_dir_entries(&self, quick_dir: &QuickDir) -> Result<&[StreamEntry], LookupError> {
+        self.get_stream_entries()
+            .get(quick_dir.range())
+            .ok_or(LookupError::CorruptIndex { table: TableKind::StreamEntries, index: quick_dir.index() as usize })
+    }
+
+    /// Every quick dir in the arc, along with its stream entries
+    fn get_stream_dirs(&self) -> Result<Vec<StreamDir<'_>>, LookupError> {
+        self.get_quick_dirs()
+            .iter()
+            .map(|quick_dir| Ok(StreamDir {
+                hash: quick_dir.hash40(),
+                entries: self.get_quick_dir_entries(quick_dir)?,
+            }))
+            .collect()
     }
 
     fn get_file_info_from_path_index(&self, path_index: FilePathIdx) -> &FileInfo {
-        let index = self.get_file_paths()[path_index].path.index() as usize;
-        let index = self.get_file_info_indices()[index].file_info_index;
+        self.try_get_file_info_from_path_index(path_index).unwrap()
+    }
+
+    /// Fallible version of [`get_file_info_from_path_index`](ArcLookup::get_file_info_from_path_index),
+    /// returning [`LookupError::CorruptIndex`] instead of panicking on an out of bounds index.
+    fn try_get_file_info_from_path_index(&self, path_index: FilePathIdx) -> Result<&FileInfo, LookupError> {
+        let file_path = checked(self.get_file_paths(), usize::from(path_index), TableKind::FilePaths)?;
+        let index = file_path.path.index() as usize;
+        let index = checked(self.get_file_info_indices(), index, TableKind::FileInfoIndices)?.file_info_index;
 
-        &self.get_file_infos()[index]
+        checked(self.get_file_infos(), usize::from(index), TableKind::FileInfos)
     }
 
     fn get_file_info_from_path_index_mut(&mut self, path_index: FilePathIdx) -> &mut FileInfo {
-        let index = self.get_file_paths()[path_index].path.index() as usize;
-        let index = self.get_file_info_indices()[index].file_info_index;
+        self.try_get_file_info_from_path_index_mut(path_index).unwrap()
+    }
+
+    /// Fallible version of [`get_file_info_from_path_index_mut`](ArcLookup::get_file_info_from_path_index_mut),
+    /// returning [`LookupError::CorruptIndex`] instead of panicking on an out of bounds index.
+    fn try_get_file_info_from_path_index_mut(&mut self, path_index: FilePathIdx) -> Result<&mut FileInfo, LookupError> {
+        let file_path = checked(self.get_file_paths(), usize::from(path_index), TableKind::FilePaths)?;
+        let index = file_path.path.index() as usize;
+        let index = checked(self.get_file_info_indices(), index, TableKind::FileInfoIndices)?.file_info_index;
 
-        &mut self.get_file_infos_mut()[index]
+        checked_mut(self.get_file_infos_mut(), usize::from(index), TableKind::FileInfos)
     }
 
     fn get_file_in_folder(&self, file_info: &FileInfo, region: Region) -> FileInfoToFileData {
-        if file_info.flags.is_regional() {
-            self.get_file_info_to_datas()[usize::from(file_info.info_to_data_index) + (region as usize)]
+        self.try_get_file_in_folder(file_info, region).unwrap()
+    }
+
+    /// Fallible version of [`get_file_in_folder`](ArcLookup::get_file_in_folder), returning
+    /// [`LookupError::CorruptIndex`] instead of panicking on an out of bounds index.
+    fn try_get_file_in_folder(&self, file_info: &FileInfo, region: Region) -> Result<FileInfoToFileData, LookupError> {
+        let index = if file_info.flags.is_regional() {
+            usize::from(file_info.info_to_data_index) + (region as usize)
         } else {
-            self.get_file_info_to_datas()[file_info.info_to_data_index]
-        }
+            usize::from(file_info.info_to_data_index)
+        };
+
+        checked(self.get_file_info_to_datas(), index, TableKind::FileInfoToDatas).copied()
     }
 
     fn get_file_in_folder_mut(&mut self, file_info: &FileInfo, region: Region) -> &mut FileInfoToFileData {
-        if file_info.flags.is_regional() {
-            &mut self.get_file_info_to_datas_mut()[usize::from(file_info.info_to_data_index) + (region as usize)]
+        self.try_get_file_in_folder_mut(file_info, region).unwrap()
+    }
+
+    /// Fallible version of [`get_file_in_folder_mut`](ArcLookup::get_file_in_folder_mut),
+    /// returning [`LookupError::CorruptIndex`] instead of panicking on an out of bounds index.
+    fn try_get_file_in_folder_mut(&mut self, file_info: &FileInfo, region: Region) -> Result<&mut FileInfoToFileData, LookupError> {
+        let index = if file_info.flags.is_regional() {
+            usize::from(file_info.info_to_data_index) + (region as usize)
         } else {
-            &mut self.get_file_info_to_datas_mut()[file_info.info_to_data_index]
-        }
+            usize::from(file_info.info_to_data_index)
+        };
+
+        checked_mut(self.get_file_info_to_datas_mut(), index, TableKind::FileInfoToDatas)
     }
 
     fn get_file_data_from_hash(&self, hash: Hash40, region: Region) -> Result<&FileData, LookupError> {
-        Ok(self.get_file_data(self.get_file_info_from_hash(hash)?, region))
+        self.try_get_file_data(self.get_file_info_from_hash(hash)?, region)
     }
 
     fn get_file_data(&self, file_info: &FileInfo, region: Region) -> &FileData {
-        let file_in_folder = self.get_file_in_folder(file_info, region);
+        self.try_get_file_data(file_info, region).unwrap()
+    }
+
+    /// Fallible version of [`get_file_data`](ArcLookup::get_file_data), returning
+    /// [`LookupError::CorruptIndex`] instead of panicking on an out of bounds index.
+    fn try_get_file_data(&self, file_info: &FileInfo, region: Region) -> Result<&FileData, LookupError> {
+        let file_in_folder = self.try_get_file_in_folder(file_info, region)?;
 
-        &self.get_file_datas()[file_in_folder.file_data_index]
+        checked(self.get_file_datas(), usize::from(file_in_folder.file_data_index), TableKind::FileDatas)
     }
 
     fn get_file_data_mut(&mut self, file_info: &FileInfo, region: Region) -> &mut FileData {
-        let file_in_folder = self.get_file_in_folder(file_info, region);
+        self.try_get_file_data_mut(file_info, region).unwrap()
+    }
+
+    /// Fallible version of [`get_file_data_mut`](ArcLookup::get_file_data_mut), returning
+    /// [`LookupError::CorruptIndex`] instead of panicking on an out of bounds index.
+    fn try_get_file_data_mut(&mut self, file_info: &FileInfo, region: Region) -> Result<&mut FileData, LookupError> {
+        let file_in_folder = self.try_get_file_in_folder(file_info, region)?;
 
-        &mut self.get_file_datas_mut()[file_in_folder.file_data_index]
+        checked_mut(self.get_file_datas_mut(), usize::from(file_in_folder.file_data_index), TableKind::FileDatas)
     }
 
     fn get_folder_offset(&self, file_info: &FileInfo, region: Region) -> u64 {
-        let file_in_folder = self.get_file_in_folder(file_info, region);
+        self.try_get_folder_offset(file_info, region).unwrap()
+    }
 
-        self.get_folder_offsets()[file_in_folder.folder_offset_index as usize].offset
+    /// Fallible version of [`get_folder_offset`](ArcLookup::get_folder_offset), returning
+    /// [`LookupError::CorruptIndex`] instead of panicking on an out of bounds index.
+    fn try_get_folder_offset(&self, file_info: &FileInfo, region: Region) -> Result<u64, LookupError> {
+        let file_in_folder = self.try_get_file_in_folder(file_info, region)?;
+        let index = file_in_folder.folder_offset_index as usize;
+
+        checked(self.get_folder_offsets(), index, TableKind::FolderOffsets).map(|folder_offset| folder_offset.offset)
     }
 
     fn get_directory_dependency(&self, dir_info: &DirInfo) -> Option<RedirectionType> {
-        if dir_info.flags.redirected() {
-            let directory_index = self.get_folder_offsets()[dir_info.path.index() as usize].directory_index;
+        self.try_get_directory_dependency(dir_info).unwrap()
+    }
 
-            if directory_index != 0xFFFFFF {
-                if dir_info.flags.is_symlink() {
-                    Some(RedirectionType::Symlink(self.get_dir_infos()[directory_index as usize]))
-                } else {
-                    Some(RedirectionType::Shared(self.get_folder_offsets()[directory_index as usize]))
+    /// Fallible version of [`get_directory_dependency`](ArcLookup::get_directory_dependency),
+    /// returning [`LookupError::CorruptIndex`] instead of panicking on an out of bounds index.
+    fn try_get_directory_dependency(&self, dir_info: &DirInfo) -> Result<Option<RedirectionType>, LookupError> {
+        let redirect = self.try_get_directory_dependency_ref(dir_info)?.map(|redirect| match redirect {
+            RedirectionRef::Symlink(dir_info) => RedirectionType::Symlink(*dir_info),
+            RedirectionRef::Shared(folder_offset) => RedirectionType::Shared(*folder_offset),
+        });
+
+        Ok(redirect)
+    }
+
+    /// Equivalent to [`try_get_directory_dependency`](ArcLookup::try_get_directory_dependency),
+    /// borrowing the directory or shared file group from the arc instead of copying it
+    fn try_get_directory_dependency_ref(&self, dir_info: &DirInfo) -> Result<Option<RedirectionRef<'_>>, LookupError> {
+        if !dir_info.flags.redirected() {
+            return Ok(None)
+        }
+
+        let folder_offsets = self.get_folder_offsets();
+        let directory_index = checked(folder_offsets, dir_info.path.index() as usize, TableKind::FolderOffsets)?.directory_index;
+
+        if directory_index == 0xFFFFFF {
+            Ok(None)
+        } else if dir_info.flags.is_symlink() {
+            let dir_info = checked(self.get_dir_infos(), directory_index as usize, TableKind::DirInfos)?;
+            Ok(Some(RedirectionRef::Symlink(dir_info)))
+        } else {
+            let folder_offset = checked(folder_offsets, directory_index as usize, TableKind::FolderOffsets)?;
+            Ok(Some(RedirectionRef::Shared(folder_offset)))
+        }
+    }
+
+    /// List the subdirectories and files of a directory by walking the directory hierarchy stored
+    /// in the arc, so unlike [`ArcFile::get_dir_listing`] the result doesn't depend on which
+    /// labels are loaded. Symlinked and shared directories also list the contents of the
+    /// directory they redirect to. Listing `"/"` gives every top-level directory.
+    fn get_dir_children<Hash: Into<Hash40>>(&self, hash: Hash) -> Result<Vec<FileNode>, LookupError> {
+        let hash = hash.into();
+
+        if hash == hash40("/") {
+            return Ok(top_level_dirs(self)
+                .map(|dir_info| FileNode::Dir(dir_info.path.hash40()))
+                .collect())
+        }
+
+        self.get_dir_info_children(self.get_dir_info_from_hash(hash)?)
+    }
+
+    /// Iterate over every file in the arc, see [`WalkdirIter`] for details
+    fn walk(&self) -> WalkdirIter<'_, Self> {
+        WalkdirIter::new(self)
+    }
+
+    /// Iterate over every file under a directory, including those of its subdirectories
+    fn walk_dir<Hash: Into<Hash40>>(&self, hash: Hash) -> Result<WalkdirIter<'_, Self>, LookupError> {
+        WalkdirIter::from_hash(self, hash)
+    }
+
+    /// Iterate over the metadata of every file in the arc, including streams, see [`FilesIter`]
+    fn files(&self, region: Region) -> FilesIter<'_, Self> {
+        FilesIter::new(self, region)
+    }
+
+    /// Follow a file's redirections to the file which owns its data, see [`ResolvedFile`]
+    fn resolve<Hash: Into<Hash40>>(&self, hash: Hash, region: Region) -> Result<ResolvedFile, LookupError> {
+        crate::resolve::resolve(self, hash.into(), region)
+    }
+
+    /// Follow a directory's symlinks to the directory or shared file group its contents come
+    /// from, see [`ResolvedDir`]
+    fn resolve_dir<Hash: Into<Hash40>>(&self, hash: Hash) -> Result<ResolvedDir, LookupError> {
+        crate::resolve::resolve_dir(self, hash.into())
+    }
+
+    /// Get a handle to a directory for navigating the arc, see [`DirRef`]
+    fn get_dir_ref<Hash: Into<Hash40>>(&self, hash: Hash) -> Result<DirRef<'_, Self>, LookupError> {
+        DirRef::from_hash(self, hash)
+    }
+
+    /// Get a handle to a file for navigating the arc, see [`FileRef`]
+    fn get_file_ref<Hash: Into<Hash40>>(&self, hash: Hash) -> Result<FileRef<'_, Self>, LookupError> {
+        FileRef::from_hash(self, hash)
+    }
+
+    /// Equivalent to [`get_dir_children`](ArcLookup::get_dir_children) for a [`DirInfo`].
+    fn get_dir_info_children(&self, dir_info: &DirInfo) -> Result<Vec<FileNode>, LookupError> {
+        let file_paths = self.get_file_paths();
+        let file_infos = self.get_file_infos();
+
+        let mut dirs = Vec::new();
+        let mut files = Vec::new();
+
+        // Symlinks can point to other symlinks, so keep track of where we've been in case they loop
+        let mut visited = HashSet::new();
+        let mut next = Some(*dir_info);
+        while let Some(dir_info) = next.take() {
+            if !visited.insert(dir_info.path.hash40()) {
+                break
+            }
+
+            let child_dirs = checked_range(self.get_folder_child_hashes(), dir_info.children_range(), TableKind::FolderChildHashes)?;
+            dirs.extend(child_dirs.iter().map(HashToIndex::hash40));
+
+            files.extend_from_slice(checked_range(file_infos, dir_info.file_info_range(), TableKind::FileInfos)?);
+
+            match self.try_get_directory_dependency(&dir_info)? {
+                Some(RedirectionType::Symlink(target)) => next = Some(target),
+                Some(RedirectionType::Shared(folder_offset)) => {
+                    files.extend_from_slice(checked_range(file_infos, folder_offset.range(), TableKind::FileInfos)?);
                 }
-            } else {
-                None
+                None => {}
             }
-        } else {
-            None
         }
+
+        let mut seen = HashSet::new();
+        let mut children: Vec<FileNode> = dirs.into_iter()
+            .filter(|dir| seen.insert(*dir))
+            .map(FileNode::Dir)
+            .collect();
+
+        for file_info in files {
+            let path = checked(file_paths, usize::from(file_info.file_path_index), TableKind::FilePaths)?.path.hash40();
+            if seen.insert(path) {
+                children.push(FileNode::File(path));
+            }
+        }
+
+        Ok(children)
     }
 
     fn read_file_data(&self, file_data: &FileData, folder_offset: u64) -> Result<Vec<u8>, LookupError> {
@@ -301,7 +706,7 @@ pub trait ArcLookup {
         let mut reader = Read::take(&mut reader, file_data.comp_size as u64);
 
         if file_data.flags.compressed() {
-            crate::zstd_backend::copy_decode(reader, &mut data)?;
+            crate::zstd_backend::copy_decode_bounded(reader, &mut data, file_data.decomp_size as u64)?;
         } else {
             io::copy(&mut reader, &mut data)?;
         }
@@ -309,84 +714,165 @@ pub trait ArcLookup {
         Ok(data)
     }
 
+    /// Read every file belonging to a directory's mass-loading group with a single sequential
+    /// read of the group, returning the decompressed files keyed by path. Files of the directory
+    /// which are stored outside of the group (such as shared files) are read individually.
+    /// Redirections of the directory are not followed.
+    fn read_directory_group<Hash: Into<Hash40>>(&self, hash: Hash, region: Region) -> Result<HashMap<Hash40, Vec<u8>>, LookupError> {
+        fn inner<Arc: ArcLookup + ?Sized>(arc: &Arc, hash: Hash40, region: Region) -> Result<HashMap<Hash40, Vec<u8>>, LookupError> {
+            let dir_info = arc.get_dir_info_from_hash(hash)?;
+            let group_index = dir_info.path.index();
+            let group = *checked(arc.get_folder_offsets(), group_index as usize, TableKind::FolderOffsets)?;
+            let group_size = group.size as u64;
+
+            if group_size > MAX_GROUP_SIZE {
+                return Err(LookupError::GroupTooLarge { index: group_index, size: group_size })
+            }
+
+            let file_infos = arc.get_file_infos()
+                .get(dir_info.file_info_range())
+                .ok_or(LookupError::CorruptIndex { table: TableKind::FileInfos, index: dir_info.file_info_start_index as usize })?;
+
+            let mut files = HashMap::new();
+            let mut in_group = Vec::new();
+            for file_info in file_infos {
+                let path = checked(arc.get_file_paths(), usize::from(file_info.file_path_index), TableKind::FilePaths)?.path.hash40();
+                let file_in_folder = arc.try_get_file_in_folder(file_info, region)?;
+                let file_data = arc.try_get_file_data(file_info, region)?;
+
+                if file_in_folder.folder_offset_index == group_index {
+                    let start = (file_data.offset_in_folder as u64) << 2;
+                    if start + file_data.comp_size as u64 > group_size {
+                        return Err(LookupError::OutsideGroup { hash: path })
+                    }
+
+                    in_group.push((path, start as usize, file_data));
+                } else {
+                    let folder_offset = arc.try_get_folder_offset(file_info, region)?;
+                    files.insert(path, arc.read_file_data(file_data, folder_offset)?);
+                }
+            }
+
+            if in_group.is_empty() {
+                return Ok(files)
+            }
+
+            let mut data = vec![0; group_size as usize];
+            {
+                let mut reader = arc.get_file_reader();
+                reader.seek(SeekFrom::Start(arc.get_file_section_offset() + group.offset))?;
+                reader.read_exact(&mut data)?;
+            }
+
+            for (path, start, file_data) in in_group {
+                let raw = &data[start..start + file_data.comp_size as usize];
+
+                let flags = file_data.flags;
+                files.insert(path, decode_raw(raw, flags.compressed(), flags.use_zstd(), file_data.decomp_size as u64)?);
+            }
+
+            Ok(files)
+        }
+
+        inner(self, hash.into(), region)
+    }
+
+    /// Read many files at once, calling `callback` with the contents of each as it is read.
+    ///
+    /// Reads are made in the order the files are stored in the arc, with nearby files merged into
+    /// a single read, rather than in the order they are given. Files which can't be found or read
+    /// are passed to `callback` as errors without stopping the batch.
+    fn read_batch<F>(&self, hashes: &[Hash40], region: Region, callback: F) -> Result<(), LookupError>
+        where F: FnMut(Hash40, Result<Vec<u8>, LookupError>),
+    {
+        self.read_batch_with_progress(hashes, region, &(), callback)
+    }
+
+    /// Equivalent to [`read_batch`](ArcLookup::read_batch) while reporting progress to `hook`.
+    /// Returns [`LookupError::Cancelled`] if the hook cancels the batch.
+    fn read_batch_with_progress<F>(&self, hashes: &[Hash40], region: Region, hook: &dyn ProgressHook, callback: F) -> Result<(), LookupError>
+        where F: FnMut(Hash40, Result<Vec<u8>, LookupError>),
+    {
+        crate::batch::read_batch(self, hashes, region, hook, callback)
+    }
+
+    /// Check that every index between the tables of the filesystem points to a valid entry, so
+    /// that lookups into the tables can't go out of bounds.
+    fn validate(&self) -> Result<(), ValidationError> {
+        crate::validation::validate(self)
+    }
+
+    /// Check every file and stream in the arc for corruption. Each file's data is decompressed
+    /// and checked against its expected size, and the data of every file and stream is checked to
+    /// lie within its section of the arc.
+    ///
+    /// Bad entries are collected into the returned report. An error is only returned if the arc
+    /// itself can't be read.
+    fn verify(&self) -> Result<VerifyReport, LookupError> {
+        self.verify_with_progress(&())
+    }
+
+    /// Equivalent to [`verify`](ArcLookup::verify) while reporting progress to `hook`.
+    /// Returns [`LookupError::Cancelled`] if the hook cancels verification.
+    fn verify_with_progress(&self, hook: &dyn ProgressHook) -> Result<VerifyReport, LookupError> {
+        crate::verify::verify(self, hook)
+    }
+
     fn get_file_offset_from_hash(&self, hash: Hash40, region: Region) -> Result<u64, LookupError> {
         let path_index = self.get_file_path_index_from_hash(hash)?;
-        let file_info = self.get_file_info_from_path_index(path_index);
-        let folder_offset = self.get_folder_offset(file_info, region);
-        let file_data = self.get_file_data(&file_info, region);
+        let file_info = self.try_get_file_info_from_path_index(path_index)?;
+        let folder_offset = self.try_get_folder_offset(file_info, region)?;
+        let file_data = self.try_get_file_data(file_info, region)?;
         let offset = folder_offset + self.get_file_section_offset() + ((file_data.offset_in_folder as u64) <<  2);
 
         Ok(offset)
     }
 
     fn get_shared_data_index(&self) -> u32 {
+        self.try_get_shared_data_index().unwrap()
+    }
+
+    /// Fallible version of [`get_shared_data_index`](ArcLookup::get_shared_data_index), returning
+    /// [`LookupError::CorruptIndex`] instead of panicking on an out of bounds index.
+    fn try_get_shared_data_index(&self) -> Result<u32, LookupError> {
         let dirs = self.get_dir_infos();
         let mut max = 0;
         let folders = self.get_folder_offsets();
         for dir in dirs.iter() {
             if dir.flags.redirected() && !dir.flags.is_symlink() {
-                let data_folder = &folders[dir.path.index() as usize];
-                if max < (data_folder.file_start_index + data_folder.file_count) {
-                    max = data_folder.file_start_index + data_folder.file_count;
-                }
+                let data_folder = checked(folders, dir.path.index() as usize, TableKind::FolderOffsets)?;
+                max = max.max(data_folder.file_start_index.saturating_add(data_folder.file_count));
             }
         }
-        max
+        Ok(max)
     }
 
     fn get_file_metadata<Hash: Into<Hash40>>(&self, hash: Hash, region: Region) -> Result<FileMetadata, LookupError> {
-        fn inner<Arc: ArcLookup + ?Sized>(arc: &Arc, hash: Hash40, region: Region) -> Result<FileMetadata, LookupError> {
-            match arc.get_file_path_index_from_hash(hash) {
-                Ok(path_index) => {
-                    let file_path = &arc.get_file_paths()[path_index];
-                    let file_info = arc.get_file_info_from_path_index(path_index);
-                    let folder_offset = arc.get_folder_offset(file_info, region);
-                    let file_data = arc.get_file_data(&file_info, region);
-
-                    let offset = folder_offset + arc.get_file_section_offset() + ((file_data.offset_in_folder as u64) <<  2);
-                    
-                    Ok(FileMetadata {
-                        path_hash: file_path.path.hash40(),
-                        ext_hash: file_path.ext.hash40(),
-                        parent_hash: file_path.parent.hash40(),
-                        file_name_hash: file_path.file_name.hash40(),
-                        offset,
-                        comp_size: file_data.comp_size as _,
-                        decomp_size: file_data.decomp_size as _,
-                        is_stream: false,
-                        is_shared: arc.get_shared_section_offset() < offset,
-                        is_redirect: file_info.flags.is_redirect(),
-                        is_regional: file_info.flags.is_regional(),
-                        is_localized: file_info.flags.is_localized(),
-                        is_compressed: file_data.flags.compressed(),
-                        uses_zstd: file_data.flags.use_zstd(),
-                    })
-                }
-                Err(LookupError::Missing) => {
-                    let stream_data = arc.get_stream_data(hash)?;
-
-                    Ok(FileMetadata {
-                        path_hash: hash,
-                        ext_hash: Hash40(0),
-                        parent_hash: Hash40(0),
-                        file_name_hash: Hash40(0),
-                        offset: stream_data.offset,
-                        comp_size: stream_data.size,
-                        decomp_size: stream_data.size,
-                        is_stream: true,
-                        is_shared: false,
-                        is_redirect: false,
-                        is_regional: false,
-                        is_localized: false,
-                        is_compressed: false,
-                        uses_zstd: false,
-                    })
-                }
-                Err(err) => Err(err)
+        let hash = hash.into();
+
+        match self.get_file_path_index_from_hash(hash) {
+            Ok(path_index) => file_metadata(self, path_index, region),
+            Err(LookupError::Missing { step: LookupStep::FileBucket, .. }) => {
+                Ok(stream_metadata(hash, self.get_stream_data(hash)?))
             }
+            Err(err) => Err(err)
         }
+    }
+}
 
-        inner(self, hash.into(), region)
+/// Decompress (if needed) the raw contents of a file which have already been read from the arc
+pub(crate) fn decode_raw(raw: &[u8], is_compressed: bool, uses_zstd: bool, decomp_size: u64) -> Result<Vec<u8>, LookupError> {
+    if is_compressed && !uses_zstd {
+        return Err(LookupError::UnsupportedCompression)
+    }
+
+    if is_compressed {
+        let mut data = Vec::with_capacity(decomp_size as usize);
+        crate::zstd_backend::copy_decode_bounded(raw, &mut data, decomp_size)?;
+
+        Ok(data)
+    } else {
+        Ok(raw.to_vec())
     }
 }
 
@@ -398,79 +884,150 @@ pub trait SearchLookup {
     fn get_path_list(&self) -> &[PathListEntry];
 
     fn get_folder_path_index_from_hash(&self, hash: impl Into<Hash40>) -> Result<&HashToIndex, LookupError> {
+        let hash = hash.into();
         let folder_path_to_index = self.get_folder_path_to_index();
-        match folder_path_to_index.binary_search_by_key(&hash.into(), |h| h.hash40()) {
+        match folder_path_to_index.binary_search_by_key(&hash, |h| h.hash40()) {
             Ok(idx) => Ok(&folder_path_to_index[idx]),
-            Err(_) => Err(LookupError::Missing)
+            Err(_) => Err(LookupError::missing(hash, LookupStep::SearchFolder))
         }
     }
 
     fn get_folder_path_entry_from_hash(&self, hash: impl Into<Hash40>) -> Result<&FolderPathListEntry, LookupError> {
+        let hash = hash.into();
         let index = self.get_folder_path_index_from_hash(hash)?;
         if index.index() != 0xFF_FFFF {
-            Ok(&self.get_folder_path_list()[index.index() as usize])
+            checked(self.get_folder_path_list(), index.index() as usize, TableKind::FolderPathList)
         } else {
-            Err(LookupError::Missing)
+            Err(LookupError::missing(hash, LookupStep::SearchFolder))
         }
     }
 
     fn get_path_index_from_hash(&self, hash: impl Into<Hash40>) -> Result<&HashToIndex, LookupError> {
+        let hash = hash.into();
         let path_to_index = self.get_path_to_index();
-        match path_to_index.binary_search_by_key(&hash.into(), |h| h.hash40()) {
+        match path_to_index.binary_search_by_key(&hash, |h| h.hash40()) {
             Ok(idx) => Ok(&path_to_index[idx]),
-            Err(_) => Err(LookupError::Missing)
+            Err(_) => Err(LookupError::missing(hash, LookupStep::SearchPath))
         }
     }
 
     fn get_path_list_index_from_hash(&self, hash: impl Into<Hash40>) -> Result<u32, LookupError> {
+        let hash = hash.into();
         let index = self.get_path_index_from_hash(hash)?;
         if index.index() != 0xFF_FFFF {
-            Ok(self.get_path_list_indices()[index.index() as usize])
+            checked(self.get_path_list_indices(), index.index() as usize, TableKind::PathListIndices).copied()
         } else {
-            Err(LookupError::Missing)
+            Err(LookupError::missing(hash, LookupStep::SearchPath))
         }
     }
 
     fn get_path_list_entry_from_hash(&self, hash: impl Into<Hash40>) -> Result<&PathListEntry, LookupError> {
+        let hash = hash.into();
         let index = self.get_path_list_index_from_hash(hash)?;
         if index != 0xFF_FFFF {
-            Ok(&self.get_path_list()[index as usize])
+            checked(self.get_path_list(), index as usize, TableKind::PathList)
         } else {
-            Err(LookupError::Missing)
+            Err(LookupError::missing(hash, LookupStep::SearchPath))
         }
     }
 
     fn get_first_child_in_folder(&self, hash: impl Into<Hash40>) -> Result<&PathListEntry, LookupError> {
+        let hash = hash.into();
         let folder_path = self.get_folder_path_entry_from_hash(hash)?;
         let index_idx = folder_path.get_first_child_index();
 
         if index_idx == 0xFF_FFFF {
-            return Err(LookupError::Missing);
+            return Err(LookupError::missing(hash, LookupStep::SearchChild));
         }
 
-        let path_entry_index = self.get_path_list_indices()[index_idx];
+        let path_entry_index = *checked(self.get_path_list_indices(), index_idx, TableKind::PathListIndices)?;
         if path_entry_index != 0xFF_FFFF {
-            Ok(&self.get_path_list()[path_entry_index as usize])
+            checked(self.get_path_list(), path_entry_index as usize, TableKind::PathList)
         } else {
-            Err(LookupError::Missing)
+            Err(LookupError::missing(hash, LookupStep::SearchChild))
         }
     }
 
     fn get_next_child_in_folder(&self, current_child: &PathListEntry) -> Result<&PathListEntry, LookupError> {
+        let hash = current_child.path.hash40();
         let index_idx = current_child.path.index() as usize;
         if index_idx == 0xFF_FFFF {
-            return Err(LookupError::Missing);
+            return Err(LookupError::missing(hash, LookupStep::SearchChild));
         }
 
-        let path_entry_index = self.get_path_list_indices()[index_idx];
+        let path_entry_index = *checked(self.get_path_list_indices(), index_idx, TableKind::PathListIndices)?;
         if path_entry_index != 0xFF_FFFF {
-            Ok(&self.get_path_list()[path_entry_index as usize])
+            checked(self.get_path_list(), path_entry_index as usize, TableKind::PathList)
         } else {
-            Err(LookupError::Missing)
+            Err(LookupError::missing(hash, LookupStep::SearchChild))
         }
     }
 }
 
+/// The hash a quick dir is stored under, given its path. Quick dirs are named by their path
+/// relative to `stream:/sound/`, or to `stream:/` for those outside of it, so both
+/// `"stream:/sound/bgm"` and `"bgm"` give the hash of `"bgm"`. Trailing slashes are ignored.
+pub fn stream_dir_hash(dir: &str) -> Hash40 {
+    let dir = dir.trim_end_matches('/');
+    let name = dir.strip_prefix("stream:/sound/")
+        .or_else(|| dir.strip_prefix("stream:/"))
+        .unwrap_or(dir);
+
+    hash40(name)
+}
+
+/// A quick dir, along with its stream entries
+#[derive(Debug, Clone, Copy)]
+pub struct StreamDir<'a> {
+    pub hash: Hash40,
+    pub entries: &'a [StreamEntry],
+}
+
+pub(crate) fn file_metadata<A: ArcLookup + ?Sized>(arc: &A, path_index: FilePathIdx, region: Region) -> Result<FileMetadata, LookupError> {
+    let file_path = checked(arc.get_file_paths(), usize::from(path_index), TableKind::FilePaths)?;
+    let file_info = arc.try_get_file_info_from_path_index(path_index)?;
+    let folder_offset = arc.try_get_folder_offset(file_info, region)?;
+    let file_data = arc.try_get_file_data(file_info, region)?;
+
+    let offset = folder_offset + arc.get_file_section_offset() + ((file_data.offset_in_folder as u64) <<  2);
+
+    Ok(FileMetadata {
+        path_hash: file_path.path.hash40(),
+        ext_hash: file_path.ext.hash40(),
+        parent_hash: file_path.parent.hash40(),
+        file_name_hash: file_path.file_name.hash40(),
+        offset,
+        comp_size: file_data.comp_size as _,
+        decomp_size: file_data.decomp_size as _,
+        is_stream: false,
+        is_shared: arc.get_shared_section_offset() < offset,
+        is_redirect: file_info.flags.is_redirect(),
+        is_regional: file_info.flags.is_regional(),
+        is_localized: file_info.flags.is_localized(),
+        is_compressed: file_data.flags.compressed(),
+        uses_zstd: file_data.flags.use_zstd(),
+    })
+}
+
+pub(crate) fn stream_metadata(hash: Hash40, stream_data: &StreamData) -> FileMetadata {
+    FileMetadata {
+        path_hash: hash,
+        ext_hash: Hash40(0),
+        parent_hash: Hash40(0),
+        file_name_hash: Hash40(0),
+        offset: stream_data.offset,
+        comp_size: stream_data.size,
+        decomp_size: stream_data.size,
+        is_stream: true,
+        is_shared: false,
+        is_redirect: false,
+        is_regional: false,
+        is_localized: false,
+        is_compressed: false,
+        uses_zstd: false,
+    }
+}
+
 #[repr(C)]
 #[derive(Debug)]
 pub struct FileMetadata {
@@ -512,7 +1069,7 @@ impl FileInfoBucket {
 impl DirInfo {
     pub fn file_info_range(self) -> Range<usize> {
         let start = self.file_info_start_index as usize;
-        let end = start + self.file_count as usize;
+        let end = start.saturating_add(self.file_count as usize);
 
         start..end
     }
@@ -526,7 +1083,6 @@ impl DirInfo {
 }
 
 impl DirectoryOffset {
-    #[allow(dead_code)]
     pub fn range(self) -> Range<usize> {
         let start = self.file_start_index as usize;
         let end = start + self.file_count as usize;
@@ -538,50 +1094,72 @@ impl DirectoryOffset {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_arc::ArcBuilder;
 
     #[test]
     fn test_get_file_data() {
-        let arc = ArcFile::open("/home/jam/re/ult/900/data.arc").unwrap();
-        let data = arc.get_file_contents("sound/config/bgm_property.bin", Region::UsEnglish).unwrap();
+        let arc = ArcBuilder::sample().open();
 
-        //std::fs::write("bgm_property.bin", data).unwrap();
+        let data = arc.get_file_contents("fighter/mario/model/body/c00/model.numdlb", Region::UsEnglish).unwrap();
+        assert_eq!(data, b"mario model");
 
-        //dbg!(arc.file_system.dirs.len());
+        let data = arc.get_file_contents("fighter/mario/model/body/c00/def_mario_001_col.nutexb", Region::UsEnglish).unwrap();
+        assert_eq!(data, [0x55; 0x300]);
     }
 
     #[test]
-    fn test_get_stream_file() {
-        let arc = ArcFile::open("/home/jam/re/ult/900/data.arc").unwrap();
-        
-        let labels = crate::hash_labels::HashLabels::from_file("/home/jam/Downloads/hashes.txt").unwrap();
-        dbg!(arc.file_system.stream_entries[0].hash40().label(&labels));
+    fn test_get_regional_file() {
+        let arc = ArcBuilder::sample().open();
 
-        let data = arc.get_file_contents("stream:/sound/bgm/bgm_a10_malrpg2_zarazarasabaku.nus3audio", Region::UsEnglish).unwrap();
+        let data = arc.get_file_contents("ui/message/msg_name.msbt", Region::UsEnglish).unwrap();
+        assert_eq!(data, b"name UsEnglish");
 
-        //std::fs::write("bgm_a10_malrpg2_zarazarasabaku.nus3audio", data).unwrap();
+        let data = arc.get_file_contents("ui/message/msg_name.msbt", Region::Japanese).unwrap();
+        assert_eq!(data, b"name Japanese");
+    }
+
+    #[test]
+    fn test_get_stream_file() {
+        let builder = ArcBuilder::sample();
+        let arc = builder.open();
+        
+        let labels = builder.labels();
+        assert_eq!(
+            arc.file_system.stream_entries[0].hash40().label(&labels),
+            Some("stream:/sound/bgm/bgm_crs2_01_menu.nus3audio")
+        );
+
+        let data = arc.get_file_contents("stream:/sound/bgm/bgm_crs2_01_menu.nus3audio", Region::UsEnglish).unwrap();
+        assert_eq!(data, b"menu music");
     }
 
     #[test]
     fn test_get_shared() {
         let hash: Hash40 = "fighter/mario/model/body/c00/leyes_eye_mario_l_col.nutexb".into();
 
-
-        let labels = crate::hash_labels::HashLabels::from_file("/home/jam/Downloads/hashes.txt").unwrap();
-        dbg!(hash.label(&labels));
-
-        let arc = ArcFile::open("/home/jam/re/ult/900/data.arc").unwrap();
+        let builder = ArcBuilder::sample();
+        let labels = builder.labels();
+        let arc = builder.open();
         let shared_files = arc.get_shared_files(hash, Region::UsEnglish).unwrap();
 
-        let shared_files: Vec<Option<&str>> = shared_files.into_iter()
+        let mut shared_files: Vec<Option<&str>> = shared_files.into_iter()
             .map(|hash| hash.label(&labels))
             .collect();
+        shared_files.sort();
 
-        dbg!(shared_files);
+        assert_eq!(shared_files, [
+            Some("fighter/luigi/model/body/c00/leyes_eye_mario_l_col.nutexb"),
+            Some("fighter/mario/model/body/c00/leyes_eye_mario_l_col.nutexb"),
+        ]);
+
+        let unshared = arc.get_shared_files("fighter/mario/model/body/c00/model.numdlb".into(), Region::UsEnglish).unwrap();
+        assert!(unshared.is_empty());
     }
 
     #[test]
     fn test_get_dir() {
-        let arc = ArcFile::open("H:/Documents/Smash/update/romfs/data_1010.arc").unwrap();
+        let builder = ArcBuilder::sample();
+        let arc = builder.open();
         let dir_info = arc.get_dir_info_from_hash("fighter/mario").unwrap();
 
         let start = dir_info.child_dir_start_index as usize;
@@ -590,23 +1168,25 @@ mod tests {
         let children = &arc.file_system.folder_child_hashes[start..end].iter()
             .map(|child| &arc.file_system.dir_infos[child.index() as usize])
             .collect::<Vec<_>>();
-        let labels = crate::hash_labels::HashLabels::from_file("H:/Downloads/hashes.txt").unwrap();
+        let labels = builder.labels();
 
-        for child in children {
-            eprint!("{} ", child.name.label(&labels).map(String::from).unwrap_or_else(|| format!("0x{:X}", child.name.as_u64())));
-            eprintln!("{}", child.parent.label(&labels).map(String::from).unwrap_or_else(|| format!("0x{:X}", child.parent.as_u64())));
-        }
+        let names: Vec<&str> = children.iter()
+            .map(|child| child.path.hash40().label(&labels).unwrap())
+            .collect();
+        assert_eq!(names, ["fighter/mario/c00", "fighter/mario/model", "fighter/mario/motion"]);
 
-        dbg!(dir_info);
+        assert!(children.iter().all(|child| { child.parent } == Hash40::from("fighter/mario")));
+        assert_eq!({ dir_info.name }, Hash40::from("mario"));
     }
 
     #[test]
     fn test_list_stream() {
-        let arc = ArcFile::open("/home/jam/re/ult/900/data.arc").unwrap();
+        let builder = ArcBuilder::sample();
+        let arc = builder.open();
 
         let mut extensions = std::collections::HashSet::new();
 
-        let labels = crate::hash_labels::HashLabels::from_file("/home/jam/Downloads/hashes.txt").unwrap();
+        let labels = builder.labels();
         for file in arc.get_stream_listing("stream:/sound/bgm").unwrap() {
             if let Some(label) = file.hash40().label(&labels) {
                 extensions.insert(label.rsplit(".").next().unwrap());
@@ -618,189 +1198,372 @@ mod tests {
         assert!(extensions.contains("nus3bank"));
     }
 
-    fn dir_info_print_filepaths(arc: &ArcFile, dir_info: &DirInfo, labels: &HashLabels) {
-        dbg!(&dir_info);
+    #[test]
+    fn stream_dirs() {
+        let mut arc = ArcBuilder::sample()
+            .stream_file("stream:/sound/fighter/mario/vc_mario.nus3audio", b"mario voice")
+            .open();
 
-        
+        let dirs: Vec<_> = arc.get_stream_dirs().unwrap().iter().map(|dir| (dir.hash, dir.entries.len())).collect();
+        assert_eq!(dirs, [(hash40("bgm"), 2), (hash40("movie"), 1), (hash40("fighter/mario"), 1)]);
 
-            let file_infos = &arc.get_file_infos()[dir_info.file_info_range()].iter().collect::<Vec<_>>();
+        for dir in ["stream:/sound/fighter/mario", "stream:/sound/fighter/mario/", "fighter/mario"] {
+            let entries = arc.get_stream_listing(dir).unwrap();
+            assert_eq!(entries[0].hash40(), hash40("stream:/sound/fighter/mario/vc_mario.nus3audio"));
+        }
+        assert_eq!(arc.get_stream_listing("stream:/movie").unwrap().len(), 1);
+
+        for dir in ["stream:/sound", "stream:", "", "stream:/sound/fighter"] {
+            assert!(matches!(
+                arc.get_stream_listing(dir),
+                Err(LookupError::Missing { step: LookupStep::QuickDir, .. })
+            ));
+        }
 
-            for infos in file_infos {
-                println!("{}", arc.get_file_paths()[infos.file_path_index].path.hash40().label(&labels).unwrap_or("Unk"));
+        arc.file_system.stream_entries[1].set_flags(0b10);
+        let flags = arc.get_stream_listing("bgm").unwrap()[1].stream_flags();
+        assert!(flags.is_regional() && !flags.is_localized());
+    }
+
+    #[test]
+    fn indexed_stream_lookups() {
+        let mut arc = ArcBuilder::sample().open();
+
+        assert_eq!(arc.get_file_contents("stream:/movie/opening.webm", Region::None).unwrap(), b"opening movie");
+        assert_eq!(arc.get_stream_data(hash40("stream:/sound/bgm/bgm_crs2_01_menu.nus3bank")).unwrap().size, 9);
+        assert!(matches!(
+            arc.get_stream_data(hash40("stream:/movie/missing.webm")),
+            Err(LookupError::Missing { step: LookupStep::StreamEntry, .. })
+        ));
+
+        arc.file_system.stream_hash_to_entries[0].set_index(0xFF_FFFF);
+        let hash = arc.file_system.stream_hash_to_entries[0].hash40();
+        assert!(matches!(
+            arc.get_stream_data(hash),
+            Err(LookupError::CorruptIndex { table: TableKind::StreamEntries, index: 0xFF_FFFF })
+        ));
+
+        // An entry found through a corrupt index must still be for the requested hash
+        let other = arc.file_system.stream_hash_to_entries[2].index();
+        arc.file_system.stream_hash_to_entries[1].set_index(other);
+        let hash = arc.file_system.stream_hash_to_entries[1].hash40();
+        assert!(matches!(
+            arc.get_stream_data(hash),
+            Err(LookupError::Missing { step: LookupStep::StreamEntry, .. })
+        ));
+    }
+
+    #[test]
+    fn sorted_stream_lookups() {
+        let paths: Vec<_> = (0..9).map(|i| format!("stream:/sound/bgm/bgm_{}.nus3audio", i)).collect();
+        let arc = paths.iter()
+            .fold(ArcBuilder::new(), |builder, path| builder.stream_file(path, path.as_bytes()))
+            .open();
+
+        let sorted = arc.get_stream_hash_to_entries();
+        assert!(sorted.windows(2).all(|pair| pair[0].hash40() < pair[1].hash40()));
+
+        // The ends of the table are where an off-by-one in the search would show up
+        for index in [0, sorted.len() / 2, sorted.len() - 1] {
+            let hash = sorted[index].hash40();
+            let path = paths.iter().find(|path| hash40(path) == hash).unwrap();
+            assert_eq!(arc.get_stream_file_contents(hash).unwrap(), path.as_bytes());
+        }
+
+        let gap = sorted.windows(2)
+            .map(|pair| (pair[0].hash40(), pair[1].hash40()))
+            .find(|(low, high)| high.as_u64() - low.as_u64() > 1)
+            .unwrap();
+        let missing = Hash40(gap.0.as_u64() + 1);
+        assert!(matches!(
+            arc.get_stream_data(missing),
+            Err(LookupError::Missing { step: LookupStep::StreamEntry, .. })
+        ));
+    }
+
+    #[test]
+    fn directory_hierarchy() {
+        let builder = ArcBuilder::sample();
+        let arc = builder.open();
+        let labels = builder.labels();
+
+        // The files of a directory, followed by those of the directory or group it redirects to
+        let file_paths = |dir: &str| -> Vec<&str> {
+            let dir = arc.get_dir_ref(dir).unwrap();
+            let mut files = dir.files().unwrap();
+            match dir.redirect().unwrap() {
+                Some(DirRedirect::Symlink(target)) => files.extend(target.files().unwrap()),
+                Some(DirRedirect::Shared(_, group)) => files.extend(group),
+                None => {}
             }
 
-            if let Some(dep) = arc.get_directory_dependency(dir_info) {
-                println!("Redirection");
-
-                match dep {
-                    RedirectionType::Symlink(dir_info) => {
-                        println!("DirInfo");
-                        dir_info_print_filepaths(arc, &dir_info, labels);
-                    },
-                    RedirectionType::Shared(dir_offs) => {
-                        println!("DirOffset");
-                        dir_offset_print_filepaths(arc, &dir_offs, labels);
-                    },
-                }
-            };
+            files.iter()
+                .map(|file| file.hash().unwrap().label(&labels).unwrap())
+                .collect()
+        };
+
+        assert_eq!(file_paths("fighter/mario/c00"), [
+            "fighter/mario/model/body/c00/model.numdlb",
+            "fighter/mario/model/body/c00/def_mario_001_col.nutexb",
+            "fighter/mario/model/body/c00/leyes_eye_mario_l_col.nutexb",
+        ]);
 
-            // println!("Printing children");
-            // dir_info_print_children(arc, dir_info, labels);
+        assert_eq!(file_paths("fighter/luigi/c00"), [
+            "fighter/luigi/model/body/c00/model.numdlb",
+            "fighter/luigi/model/body/c00/leyes_eye_mario_l_col.nutexb",
+        ]);
     }
 
-    fn dir_offset_print_filepaths(arc: &ArcFile, dir_info: &DirectoryOffset, labels: &HashLabels) {
-        dbg!(&dir_info);
-        let start = dir_info.file_start_index as usize;
-        let end = (dir_info.file_start_index as usize) + (dir_info.file_count as usize);
+    #[test]
+    fn dir_children() {
+        let builder = ArcBuilder::sample();
+        let labels = builder.labels();
+        let data = builder.build();
+        let arc = ArcFileRef::new(&data).unwrap();
+
+        let children = |dir: &str| -> Vec<String> {
+            arc.get_dir_children(dir)
+                .unwrap()
+                .into_iter()
+                .map(|node| match node {
+                    FileNode::Dir(dir) => format!("{}/", dir.label(&labels).unwrap()),
+                    FileNode::File(file) => file.label(&labels).unwrap().to_owned(),
+                })
+                .collect()
+        };
 
-        let file_infos = &arc.get_file_infos()[start..end].iter().collect::<Vec<_>>();
+        assert_eq!(children("/"), ["fighter/", "ui/"]);
+        assert_eq!(children("fighter/mario"), ["fighter/mario/c00/", "fighter/mario/model/", "fighter/mario/motion/"]);
+        assert_eq!(children("fighter/mario/model/body/c00"), [
+            "fighter/mario/model/body/c00/model.numdlb",
+            "fighter/mario/model/body/c00/def_mario_001_col.nutexb",
+            "fighter/mario/model/body/c00/leyes_eye_mario_l_col.nutexb",
+        ]);
+
+        // Redirected directories list the contents of their target
+        assert_eq!(children("fighter/mario/c00"), children("fighter/mario/model/body/c00"));
+        assert_eq!(children("fighter/luigi/c00"), [
+            "fighter/luigi/model/body/c00/model.numdlb",
+            "fighter/luigi/model/body/c00/leyes_eye_mario_l_col.nutexb",
+        ]);
+
+        assert!(matches!(
+            arc.get_dir_children("fighter/wario"),
+            Err(LookupError::Missing { step: LookupStep::DirInfo, .. })
+        ));
+
+        // A symlink to itself
+        let mut arc = builder.open();
+        let symlink = arc.file_system.dir_infos.iter().position(|dir| dir.flags.is_symlink()).unwrap();
+        let group = arc.file_system.dir_infos[symlink].path.index() as usize;
+        arc.file_system.folder_offsets[group].directory_index = symlink as u32;
+        assert!(arc.get_dir_info_children(&arc.file_system.dir_infos[symlink]).unwrap().is_empty());
+    }
 
-        for infos in file_infos {
-            println!("{}", arc.get_file_paths()[infos.file_path_index].path.hash40().label(&labels).unwrap());
-        }
+    #[test]
+    fn missing_lookups_report_step() {
+        let arc = ArcBuilder::sample().open();
+
+        assert!(matches!(
+            arc.get_nonstream_file_contents("fighter/mario/missing.numdlb", Region::Japanese),
+            Err(LookupError::Missing { step: LookupStep::FileBucket, region: Some(Region::Japanese), .. })
+        ));
+        assert!(matches!(
+            arc.get_file_contents("fighter/mario/missing.numdlb", Region::UsEnglish),
+            Err(LookupError::Missing { step: LookupStep::StreamEntry, .. })
+        ));
+        assert!(matches!(
+            arc.get_dir_info_from_hash("fighter/kirby"),
+            Err(LookupError::Missing { step: LookupStep::DirInfo, .. })
+        ));
+        assert!(matches!(
+            arc.get_stream_listing("stream:/sound/fighter"),
+            Err(LookupError::Missing { step: LookupStep::QuickDir, .. })
+        ));
     }
 
-    fn dir_info_print_children(arc: &ArcFile, dir_info: &DirInfo, labels: &HashLabels) {
-        let start = dir_info.child_dir_start_index as usize;
-        let end = (dir_info.child_dir_start_index as usize) + (dir_info.child_dir_count as usize);
+    #[test]
+    fn corrupt_indices_are_errors() {
+        let mut arc = ArcBuilder::sample().open();
+        let path = Hash40::from("fighter/mario/model/body/c00/model.numdlb");
+
+        let path_index = arc.get_file_path_index_from_hash(path).unwrap();
+        arc.get_file_info_from_path_index_mut(path_index).info_to_data_index = InfoToDataIdx(0x1000);
+
+        let file_info = *arc.get_file_info_from_hash(path).unwrap();
+        assert!(matches!(
+            arc.try_get_file_in_folder(&file_info, Region::UsEnglish),
+            Err(LookupError::CorruptIndex { table: TableKind::FileInfoToDatas, index: 0x1000 })
+        ));
+        assert!(matches!(
+            arc.get_file_contents(path, Region::UsEnglish),
+            Err(LookupError::CorruptIndex { table: TableKind::FileInfoToDatas, .. })
+        ));
+        assert!(matches!(
+            arc.try_get_file_in_folder_mut(&file_info, Region::UsEnglish),
+            Err(LookupError::CorruptIndex { table: TableKind::FileInfoToDatas, index: 0x1000 })
+        ));
+        assert!(matches!(
+            arc.try_get_file_data_mut(&file_info, Region::UsEnglish),
+            Err(LookupError::CorruptIndex { table: TableKind::FileInfoToDatas, index: 0x1000 })
+        ));
+
+        let info_index = arc.get_file_paths()[path_index].path.index() as usize;
+        arc.file_system.file_info_indices[info_index].file_info_index = FileInfoIdx(0x1000);
+        assert!(matches!(
+            arc.try_get_file_info_from_path_index_mut(path_index),
+            Err(LookupError::CorruptIndex { table: TableKind::FileInfos, index: 0x1000 })
+        ));
+
+        let luigi = arc.get_dir_info_from_hash("fighter/luigi/c00").unwrap().path.index();
+        arc.get_dir_info_from_hash_mut("fighter/luigi/c00").unwrap().path.set_index(0x1000);
+        assert!(matches!(
+            arc.try_get_shared_data_index(),
+            Err(LookupError::CorruptIndex { table: TableKind::FolderOffsets, index: 0x1000 })
+        ));
+        arc.get_dir_info_from_hash_mut("fighter/luigi/c00").unwrap().path.set_index(luigi);
+
+        let dir = arc.get_dir_info_from_hash_mut("fighter/mario/c00").unwrap();
+        let group = dir.path.index() as usize;
+        arc.file_system.folder_offsets[group].directory_index = 0x1000;
+        let dir = *arc.get_dir_info_from_hash("fighter/mario/c00").unwrap();
+        assert!(matches!(
+            arc.try_get_directory_dependency(&dir),
+            Err(LookupError::CorruptIndex { table: TableKind::DirInfos, index: 0x1000 })
+        ));
+    }
 
-        let children = &arc.file_system.folder_child_hashes[start..end].iter()
-            .map(|child| &arc.file_system.dir_infos[child.index() as usize])
-            .collect::<Vec<_>>();
+    #[test]
+    fn open_file() {
+        let arc = ArcBuilder::sample().open();
+
+        let mut data = Vec::new();
+        arc.open_file("fighter/mario/model/body/c00/def_mario_001_col.nutexb", Region::UsEnglish)
+            .unwrap()
+            .read_to_end(&mut data)
+            .unwrap();
+        assert_eq!(data, [0x55; 0x300]);
+
+        assert!(matches!(
+            arc.open_file_seekable("fighter/mario/model/body/c00/def_mario_001_col.nutexb", Region::UsEnglish),
+            Err(LookupError::NotSeekable)
+        ));
+
+        let mut file = arc.open_file_seekable("stream:/movie/opening.webm", Region::UsEnglish).unwrap();
+        let mut data = String::new();
+        file.seek(SeekFrom::Start(8)).unwrap();
+        file.read_to_string(&mut data).unwrap();
+        assert_eq!(data, "movie");
+    }
+
+    #[test]
+    fn read_file_range() {
+        let arc = ArcBuilder::sample().open();
+
+        let data = arc.read_file_range("fighter/mario/model/body/c00/model.numdlb", Region::UsEnglish, 6, 100).unwrap();
+        assert_eq!(data, b"model");
 
-        for &child in children {
-            dir_info_print_filepaths(arc, child, labels);
+        let data = arc.read_file_range("fighter/mario/model/body/c00/def_mario_001_col.nutexb", Region::UsEnglish, 0x2f0, 0x20).unwrap();
+        assert_eq!(data, [0x55; 0x10]);
+
+        for &(offset, len) in &[(0x300, 0x10), (u64::MAX, u64::MAX), (0x10, 0)] {
+            let data = arc.read_file_range("fighter/mario/model/body/c00/def_mario_001_col.nutexb", Region::UsEnglish, offset, len).unwrap();
+            assert!(data.is_empty());
         }
     }
 
     #[test]
-    fn print_directory_hierarchy() {
-        let arc = ArcFile::open("H:/Documents/Smash/update/romfs/data_1010.arc").unwrap();
-        let labels = crate::hash_labels::HashLabels::from_file("H:/Downloads/hashes.txt").unwrap();
+    fn read_file_into() {
+        let arc = ArcBuilder::sample().open();
+
+        let mut buf = [0; 0x10];
+        assert_eq!(arc.read_file_into("fighter/mario/motion/body/c00/a00wait1.nuanmb", Region::UsEnglish, &mut buf).unwrap(), 4);
+        assert_eq!(&buf[..4], b"wait");
 
-        let dir_info = dbg!(arc.get_dir_info_from_hash(Hash40::from("fighter/jack/c00")).unwrap());
+        assert!(matches!(
+            arc.read_file_into("fighter/mario/model/body/c00/def_mario_001_col.nutexb", Region::UsEnglish, &mut buf),
+            Err(LookupError::BufferTooSmall { needed: 0x300, provided: 0x10 })
+        ));
 
-        println!("Files:");
-        dir_info_print_filepaths(&arc, &dir_info, &labels); 
+        let mut buf = vec![1; 0x1000];
+        arc.read_file_into_vec("fighter/mario/model/body/c00/def_mario_001_col.nutexb", Region::UsEnglish, &mut buf).unwrap();
+        assert_eq!(buf, [0x55; 0x300]);
     }
-}
 
-// #[derive(Copy, Clone)]
-// enum WalkdirDirectoryType<'a> {
-//     FileGroup(&'a DirectoryOffset),
-//     Directory(&'a DirInfo)
-// }
-
-// pub struct WalkdirIter<'a> {
-//     arc: &'a LoadedArc,
-//     current: WalkdirDirectoryType<'a>,
-//     index: usize,
-//     child_index: usize,
-//     redirect: Option<WalkdirIter<'a, T>>,
-//     child: Option<WalkdirIter<'a, T>>
-// }
-
-// impl<'a> WalkdirIter<'a> {
-//     fn get_file_count(&self) -> usize {
-//         match self.current {
-//             WalkdirDirectoryType::Directory(directory) => directory.file_count as usize,
-//             WalkdirDirectoryType::FileGroup(group) => group.file_count as usize
-//         }
-//     }
-
-//     fn setup_redirect(&mut self) {
-//         if self.redirect.is_none() {
-//             if let WalkdirDirectoryType::Directory(directory) = self.current {
-//                 if directory.flags.redirected() {
-//                     let file_group = &arc.get_folder_offsets()[directory.path.index() as usize];
-//                     if file_group.directory_index == 0xFF_FFFF { return; }
-//                     if directory.flags.is_symlink() {
-//                         self.redirect = Self {
-//                             arc: self.arc,
-//                             current: WalkdirDirectoryType::Directory(&arc.get_dir_infos()[file_group.directory_index as usize]),
-//                             index: 0,
-//                             child_index: 0,
-//                             redirect: None,
-//                             child: None
-//                         };
-//                     } else {
-//                         self.redirect = Self {
-//                             arc: self.arc,
-//                             current: WalkdirDirectoryType::FileGroup(&arc.get_folder_offsets()[file_group.directory_index as usize]),
-//                             index: 0,
-//                             child_index: 0,
-//                             redirect: None,
-//                             child: None
-//                         };
-//                     }
-//                 }
-//             }
-//         }
-//     }
-
-//     fn setup_children(&mut self) {
-//         if let WalkdirDirectoryType::Directory(directory) = self.current {
-//             if self.child_index < directory.child_dir_count {
-                
-//             }
-//         }
-//     }
-
-//     pub fn new(arc: &'a LoadedArc, index: u32) -> Self {
-//         Self {
-//             arc,
-//             current: WalkdirDirectoryType::Directory(&arc.get_dir_infos()[index as usize]),
-//             index: 0,
-//             child_index: 0,
-//             redirect: None,
-//             child: None
-//         }
-//     }
-
-//     pub fn new_hash(arc: &'a LoadedArc, hash: Hash40) -> Self {
-//         Self {
-//             arc,
-//             current: WalkdirDirectoryType::Directory(arc.get_dir_info_from_hash(hash).unwrap()),
-//             index: 0,
-//             child_index: 0,
-//             redirect: None,
-//             child: None
-//         }
-//     }
-// }
-
-// impl<'a> Iterator for WalkdirIter<'a> {
-//     type Output = WalkdirEntry<'a>;
-
-//     fn next(&mut self) -> Option<Self::Output> {
-//         if self.index < self.get_file_count() {
-//             self.index += 1;
-//             Some(WalkdirEntry {
-//                 arc: self.arc,
-//                 parent: self.current,
-//                 index: index - 1
-//             })
-//         } else {
-            
-//         }
-//     }
-// }
-
-// pub struct WalkdirEntry<'a> {
-//     arc: &'a T,
-//     parent: WalkdirDirectoryType<'a>,
-//     index: usize
-// }
-
-// impl<'a> WalkdirEntry<'a> {
-//     pub fn parent(&self) -> WalkdirDirectoryType<'a> {
-//         self.parent
-//     }
-
-//     pub fn get(&self) -> &'a FileInfo {
-//         let range = match self.parent {
-//             WalkdirDirectoryType::FileGroup(group) => group.range(),
-//             WalkdirDirectoryType::Directory(directory) => directory.file_info_range()
-//         };
-//     }
-// }
\ No newline at end of file
+    #[test]
+    fn read_directory_group() {
+        let arc = ArcBuilder::sample().open();
+
+        let files = arc.read_directory_group("fighter/mario/model/body/c00", Region::UsEnglish).unwrap();
+        assert_eq!(files.len(), 3);
+        assert_eq!(files[&Hash40::from("fighter/mario/model/body/c00/model.numdlb")], b"mario model");
+        assert_eq!(files[&Hash40::from("fighter/mario/model/body/c00/def_mario_001_col.nutexb")], [0x55; 0x300]);
+        assert_eq!(files[&Hash40::from("fighter/mario/model/body/c00/leyes_eye_mario_l_col.nutexb")], b"shared eyes");
+
+        // Sizes come from the group rather than the files in it, and are checked before reading
+        let mut arc = ArcBuilder::sample().open();
+        let group = arc.get_dir_info_from_hash("fighter/mario/model/body/c00").unwrap().path.index() as usize;
+        arc.get_folder_offsets_mut()[group].size = 0x10;
+        assert!(matches!(
+            arc.read_directory_group("fighter/mario/model/body/c00", Region::UsEnglish),
+            Err(LookupError::OutsideGroup { .. })
+        ));
+
+        arc.get_folder_offsets_mut()[group].size = u32::MAX;
+        assert!(matches!(
+            arc.read_directory_group("fighter/mario/model/body/c00", Region::UsEnglish),
+            Err(LookupError::GroupTooLarge { size, .. }) if size == u32::MAX as u64
+        ));
+    }
+
+    #[test]
+    fn read_batch() {
+        let arc = ArcBuilder::sample().open();
+        let hashes = [
+            Hash40::from("stream:/sound/bgm/bgm_crs2_01_menu.nus3bank"),
+            Hash40::from("fighter/luigi/model/body/c00/model.numdlb"),
+            Hash40::from("fighter/mario/missing.numdlb"),
+            Hash40::from("fighter/mario/model/body/c00/model.numdlb"),
+        ];
+
+        let mut files = HashMap::new();
+        arc.read_batch(&hashes, Region::UsEnglish, |hash, data| {
+            files.insert(hash, data.ok());
+        })
+        .unwrap();
+
+        assert_eq!(files.len(), 4);
+        assert_eq!(files[&hashes[0]].as_deref(), Some(&b"menu bank"[..]));
+        assert_eq!(files[&hashes[1]].as_deref(), Some(&b"luigi model"[..]));
+        assert_eq!(files[&hashes[2]], None);
+        assert_eq!(files[&hashes[3]].as_deref(), Some(&b"mario model"[..]));
+
+        // A corrupt size fails that file alone rather than being allocated or failing the batch
+        let mut arc = arc;
+        let file_info = *arc.get_file_info_from_hash(hashes[1]).unwrap();
+        arc.get_file_data_mut(&file_info, Region::None).comp_size = u32::MAX;
+
+        let mut files = HashMap::new();
+        arc.read_batch(&hashes, Region::UsEnglish, |hash, data| {
+            files.insert(hash, data);
+        })
+        .unwrap();
+
+        assert!(matches!(files[&hashes[1]], Err(LookupError::FileRead(_))));
+        assert_eq!(files[&hashes[3]].as_deref().ok(), Some(&b"mario model"[..]));
+
+        struct Cancel;
+        impl ProgressHook for Cancel {
+            fn on_progress(&self, _: Progress) {}
+
+            fn is_cancelled(&self) -> bool {
+                true
+            }
+        }
+
+        assert!(matches!(
+            arc.read_batch_with_progress(&hashes, Region::UsEnglish, &Cancel, |_, _| ()),
+            Err(LookupError::Cancelled)
+        ));
+    }
+}
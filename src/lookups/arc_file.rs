@@ -35,6 +35,7 @@ impl ArcLookup for ArcFile {
     }
 
     fn get_file_infos_mut(&mut self) -> &mut [FileInfo] {
+        self.reverse_index.take();
         &mut self.file_system.file_infos
     }
 
@@ -43,6 +44,7 @@ impl ArcLookup for ArcFile {
     }
 
     fn get_file_info_to_datas_mut(&mut self) -> &mut [FileInfoToFileData] {
+        self.reverse_index.take();
         &mut self.file_system.file_info_to_datas
     }
 
@@ -62,6 +64,10 @@ impl ArcLookup for ArcFile {
         &mut self.file_system.folder_offsets
     }
 
+    fn get_folder_child_hashes(&self) -> &[HashToIndex] {
+        &self.file_system.folder_child_hashes
+    }
+
     fn get_stream_entries(&self) -> &[StreamEntry] {
         &self.file_system.stream_entries
     }
@@ -97,6 +103,17 @@ impl ArcLookup for ArcFile {
     fn get_file_reader<'a>(&'a self) -> Box<dyn SeekRead + 'a> {
         Box::new(MutexReader(self.reader.lock().unwrap()))
     }
+
+    /// Uses the [`reverse_index`](ArcFile::reverse_index) rather than going through every file
+    fn get_shared_files(&self, hash: Hash40, region: Region) -> Result<Vec<Hash40>, LookupError> {
+        if !self.get_file_metadata(hash, region)?.is_shared {
+            return Ok(Vec::new())
+        }
+
+        let file_in_folder = self.try_get_file_in_folder(self.get_file_info_from_hash(hash)?, region)?;
+
+        Ok(self.get_file_data_paths(file_in_folder.file_data_index, region))
+    }
 }
 
 use std::sync::MutexGuard;
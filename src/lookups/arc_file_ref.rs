@@ -0,0 +1,104 @@
+use std::io;
+use crate::*;
+
+impl ArcLookup for ArcFileRef<'_> {
+    fn get_file_info_buckets(&self) -> &[FileInfoBucket] {
+        &self.file_info_buckets
+    }
+
+    fn get_file_hash_to_path_index(&self) -> &[HashToIndex] {
+        &self.file_hash_to_path_index
+    }
+
+    fn get_dir_hash_to_info_index(&self) -> &[HashToIndex] {
+        &self.dir_hash_to_info_index
+    }
+
+    fn get_dir_infos(&self) -> &[DirInfo] {
+        &self.dir_infos
+    }
+
+    fn get_dir_infos_mut(&mut self) -> &mut [DirInfo] {
+        self.dir_infos.to_mut()
+    }
+
+    fn get_file_paths(&self) -> &[FilePath] {
+        &self.file_paths
+    }
+
+    fn get_file_info_indices(&self) -> &[FileInfoIndex] {
+        &self.file_info_indices
+    }
+
+    fn get_file_infos(&self) -> &[FileInfo] {
+        &self.file_infos
+    }
+
+    fn get_file_infos_mut(&mut self) -> &mut [FileInfo] {
+        self.file_infos.to_mut()
+    }
+
+    fn get_file_info_to_datas(&self) -> &[FileInfoToFileData] {
+        &self.file_info_to_datas
+    }
+
+    fn get_file_info_to_datas_mut(&mut self) -> &mut [FileInfoToFileData] {
+        self.file_info_to_datas.to_mut()
+    }
+
+    fn get_file_datas(&self) -> &[FileData] {
+        &self.file_datas
+    }
+
+    fn get_file_datas_mut(&mut self) -> &mut [FileData] {
+        self.file_datas.to_mut()
+    }
+
+    fn get_folder_offsets(&self) -> &[DirectoryOffset] {
+        &self.folder_offsets
+    }
+
+    fn get_folder_offsets_mut(&mut self) -> &mut [DirectoryOffset] {
+        self.folder_offsets.to_mut()
+    }
+
+    fn get_folder_child_hashes(&self) -> &[HashToIndex] {
+        &self.folder_child_hashes
+    }
+
+    fn get_stream_entries(&self) -> &[StreamEntry] {
+        &self.stream_entries
+    }
+
+    fn get_stream_file_indices(&self) -> &[u32] {
+        &self.stream_file_indices
+    }
+
+    fn get_stream_datas(&self) -> &[StreamData] {
+        &self.stream_datas
+    }
+
+    fn get_stream_hash_to_entries(&self) -> &[HashToIndex] {
+        &self.stream_hash_to_entries
+    }
+
+    fn get_quick_dirs(&self) -> &[QuickDir] {
+        &self.quick_dirs
+    }
+
+    fn get_file_section_offset(&self) -> u64 {
+        self.file_section_offset
+    }
+
+    fn get_stream_section_offset(&self) -> u64 {
+        self.stream_section_offset
+    }
+
+    fn get_shared_section_offset(&self) -> u64 {
+        self.shared_section_offset
+    }
+
+    fn get_file_reader<'a>(&'a self) -> Box<dyn SeekRead + 'a> {
+        Box::new(io::Cursor::new(self.data))
+    }
+}
@@ -129,6 +129,14 @@ impl ArcLookup for LoadedArc {
         }
     }
 
+    fn get_folder_child_hashes(&self) -> &[HashToIndex] {
+        unsafe {
+            let fs = *self.fs_header;
+            let table_size = fs.hash_folder_count;
+            slice::from_raw_parts(self.folder_child_hashes, table_size as _)
+        }
+    }
+
     fn get_stream_entries(&self) -> &[StreamEntry] {
         unsafe {
             let stream = &*self.stream_header;
@@ -0,0 +1,138 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{Hash40, LookupError};
+
+/// A snapshot of how far along a long-running operation is
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    pub files_done: u64,
+    pub files_total: u64,
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+    /// The file which was most recently processed
+    pub current: Hash40,
+}
+
+/// A hook for following the progress of long-running operations, such as extraction or
+/// verification, and for cancelling them.
+///
+/// `on_progress` is called after each file is processed and `is_cancelled` is checked between
+/// files. Operations which process files in parallel may call both from multiple threads.
+///
+/// Closures taking a [`Progress`] can be used as a hook, and `()` can be used to ignore progress.
+pub trait ProgressHook: Sync {
+    fn on_progress(&self, progress: Progress);
+
+    fn is_cancelled(&self) -> bool {
+        false
+    }
+}
+
+impl ProgressHook for () {
+    fn on_progress(&self, _: Progress) {}
+}
+
+impl<F: Fn(Progress) + Sync> ProgressHook for F {
+    fn on_progress(&self, progress: Progress) {
+        self(progress)
+    }
+}
+
+/// Shared counters for reporting progress to a [`ProgressHook`] from any number of threads
+pub(crate) struct ProgressCounter<'a> {
+    hook: &'a dyn ProgressHook,
+    files_total: u64,
+    bytes_total: u64,
+    files_done: AtomicU64,
+    bytes_done: AtomicU64,
+}
+
+impl<'a> ProgressCounter<'a> {
+    pub(crate) fn new(hook: &'a dyn ProgressHook, files_total: u64, bytes_total: u64) -> Self {
+        Self {
+            hook,
+            files_total,
+            bytes_total,
+            files_done: AtomicU64::new(0),
+            bytes_done: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns [`LookupError::Cancelled`] if the hook has requested the operation stop
+    pub(crate) fn check_cancelled(&self) -> Result<(), LookupError> {
+        if self.hook.is_cancelled() {
+            Err(LookupError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub(crate) fn file_done(&self, current: Hash40, bytes: u64) {
+        let files_done = self.files_done.fetch_add(1, Ordering::Relaxed) + 1;
+        let bytes_done = self.bytes_done.fetch_add(bytes, Ordering::Relaxed) + bytes;
+
+        self.hook.on_progress(Progress {
+            files_done,
+            files_total: self.files_total,
+            bytes_done,
+            bytes_total: self.bytes_total,
+            current,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicBool;
+
+    struct CancelAfter(u64, AtomicU64, AtomicBool);
+
+    impl ProgressHook for CancelAfter {
+        fn on_progress(&self, progress: Progress) {
+            self.1.store(progress.files_done, Ordering::Relaxed);
+            if progress.files_done >= self.0 {
+                self.2.store(true, Ordering::Relaxed);
+            }
+        }
+
+        fn is_cancelled(&self) -> bool {
+            self.2.load(Ordering::Relaxed)
+        }
+    }
+
+    #[test]
+    fn counts_and_cancels() {
+        let hook = CancelAfter(2, AtomicU64::new(0), AtomicBool::new(false));
+        let counter = ProgressCounter::new(&hook, 3, 30);
+
+        counter.check_cancelled().unwrap();
+        counter.file_done(Hash40(1), 10);
+        counter.check_cancelled().unwrap();
+        counter.file_done(Hash40(2), 10);
+
+        assert!(matches!(counter.check_cancelled(), Err(LookupError::Cancelled)));
+        assert_eq!(hook.1.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn closure_hook() {
+        let last = std::sync::Mutex::new(None);
+        let hook = |progress: Progress| *last.lock().unwrap() = Some(progress);
+        let counter = ProgressCounter::new(&hook, 1, 5);
+
+        counter.file_done(Hash40(7), 5);
+
+        assert_eq!(
+            *last.lock().unwrap(),
+            Some(Progress {
+                files_done: 1,
+                files_total: 1,
+                bytes_done: 5,
+                bytes_total: 5,
+                current: Hash40(7),
+            })
+        );
+    }
+}
@@ -1,5 +1,9 @@
 use std::str::FromStr;
 use std::convert::Infallible;
+
+/// The number of regions, including [`Region::None`]. Regional files have a file data for each.
+pub(crate) const REGION_COUNT: usize = 15;
+
 #[repr(u32)]
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Region {
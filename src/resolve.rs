@@ -0,0 +1,192 @@
+use std::collections::HashSet;
+
+use crate::lookups::checked;
+use crate::{
+    ArcLookup, DirInfo, DirectoryOffset, FileData, FileDataIdx, FileInfo, Hash40, LookupError, RedirectionRef,
+    Region, TableKind,
+};
+
+/// Where a file's data ends up coming from, created by [`ArcLookup::resolve`]
+#[derive(Debug, Clone)]
+pub struct ResolvedFile {
+    /// The path which was resolved
+    pub path: Hash40,
+    /// The path of each file the lookup was redirected to, in order. The last one is the file
+    /// which owns the data. Empty if the file isn't redirected.
+    pub redirects: Vec<Hash40>,
+    /// The file info which owns the data
+    pub file_info: FileInfo,
+    pub file_data_index: FileDataIdx,
+    pub file_data: FileData,
+    /// The index of the mass-loading group the data is stored in
+    pub folder_offset_index: u32,
+    pub folder_offset: DirectoryOffset,
+}
+
+impl ResolvedFile {
+    /// Whether the data belongs to a different file than the one which was resolved
+    pub fn is_redirected(&self) -> bool {
+        !self.redirects.is_empty()
+    }
+}
+
+/// Where a directory's contents end up coming from, created by [`ArcLookup::resolve_dir`]
+#[derive(Debug, Clone)]
+pub struct ResolvedDir {
+    /// The path which was resolved
+    pub path: Hash40,
+    /// The path of each directory the lookup was symlinked to, in order
+    pub symlinks: Vec<Hash40>,
+    /// The directory the symlinks end at, or the original directory if there were none
+    pub dir_info: DirInfo,
+    /// The shared file group `dir_info` redirects to, if any
+    pub shared: Option<DirectoryOffset>,
+}
+
+pub(crate) fn resolve<A: ArcLookup + ?Sized>(arc: &A, hash: Hash40, region: Region) -> Result<ResolvedFile, LookupError> {
+    let mut file_info = *arc.get_file_info_from_hash(hash).map_err(|err| err.in_region(region))?;
+    let mut redirects = Vec::new();
+
+    // Malformed arcs can redirect in a loop, so stop if a file comes up a second time
+    let mut visited = HashSet::new();
+    visited.insert(hash);
+
+    loop {
+        let file_in_folder = arc.try_get_file_in_folder(&file_info, region)?;
+
+        if !file_info.flags.is_redirect() {
+            let folder_offset_index = file_in_folder.folder_offset_index;
+            let folder_offset = *checked(arc.get_folder_offsets(), folder_offset_index as usize, TableKind::FolderOffsets)?;
+            let file_data = *checked(arc.get_file_datas(), usize::from(file_in_folder.file_data_index), TableKind::FileDatas)?;
+
+            return Ok(ResolvedFile {
+                path: hash,
+                redirects,
+                file_info,
+                file_data_index: file_in_folder.file_data_index,
+                file_data,
+                folder_offset_index,
+                folder_offset,
+            })
+        }
+
+        let target_index = file_in_folder.file_info_index_and_load_type.file_info_idx() as usize;
+        file_info = *checked(arc.get_file_infos(), target_index, TableKind::FileInfos)?;
+
+        let target = checked(arc.get_file_paths(), usize::from(file_info.file_path_index), TableKind::FilePaths)?.path.hash40();
+        if !visited.insert(target) {
+            return Err(LookupError::RedirectLoop { hash, redirect: target })
+        }
+
+        redirects.push(target);
+    }
+}
+
+pub(crate) fn resolve_dir<A: ArcLookup + ?Sized>(arc: &A, hash: Hash40) -> Result<ResolvedDir, LookupError> {
+    let mut dir_info = *arc.get_dir_info_from_hash(hash)?;
+    let mut symlinks = Vec::new();
+
+    let mut visited = HashSet::new();
+    visited.insert(hash);
+
+    loop {
+        let target = match arc.try_get_directory_dependency_ref(&dir_info)? {
+            Some(RedirectionRef::Symlink(target)) => target,
+            Some(RedirectionRef::Shared(shared)) => {
+                return Ok(ResolvedDir { path: hash, symlinks, dir_info, shared: Some(*shared) })
+            }
+            None => return Ok(ResolvedDir { path: hash, symlinks, dir_info, shared: None }),
+        };
+
+        dir_info = *target;
+
+        let target = dir_info.path.hash40();
+        if !visited.insert(target) {
+            return Err(LookupError::RedirectLoop { hash, redirect: target })
+        }
+
+        symlinks.push(target);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_arc::ArcBuilder;
+    use crate::{hash40, ArcFileRef};
+
+    #[test]
+    fn resolve_files() {
+        let data = ArcBuilder::sample()
+            .redirect_file("fighter/mario/model/body/c01/model.numdlb", "fighter/mario/model/body/c02/model.numdlb")
+            .redirect_file("fighter/mario/model/body/c02/model.numdlb", "fighter/mario/model/body/c00/model.numdlb")
+            .build();
+        let arc = ArcFileRef::new(&data).unwrap();
+
+        let resolved = arc.resolve("fighter/mario/model/body/c00/model.numdlb", Region::None).unwrap();
+        assert!(!resolved.is_redirected());
+        let owner = resolved.file_data_index;
+
+        let resolved = arc.resolve("fighter/mario/model/body/c01/model.numdlb", Region::None).unwrap();
+        assert_eq!(resolved.path, hash40("fighter/mario/model/body/c01/model.numdlb"));
+        assert_eq!(resolved.redirects, [
+            hash40("fighter/mario/model/body/c02/model.numdlb"),
+            hash40("fighter/mario/model/body/c00/model.numdlb"),
+        ]);
+        assert_eq!(resolved.file_data_index, owner);
+        assert_eq!(resolved.file_data.decomp_size, b"mario model".len() as u32);
+        assert_eq!(arc.get_file_contents("fighter/mario/model/body/c01/model.numdlb", Region::None).unwrap(), b"mario model");
+
+        let resolved = arc.resolve("ui/message/msg_name.msbt", Region::Korean).unwrap();
+        assert_eq!(resolved.file_data.decomp_size, b"name Korean".len() as u32);
+    }
+
+    #[test]
+    fn redirect_loop() {
+        let mut arc = ArcBuilder::sample()
+            .redirect_file("fighter/mario/model/body/c01/model.numdlb", "fighter/mario/model/body/c00/model.numdlb")
+            .open();
+
+        // Make the target redirect back to the file it was redirected from
+        let from = *arc.get_file_info_from_hash(hash40("fighter/mario/model/body/c01/model.numdlb")).unwrap();
+        let from_index = arc.get_file_in_folder(&from, Region::None).file_info_index_and_load_type.file_info_idx();
+        let target_index = arc.get_file_infos().iter().position(|info| info.file_path_index == from.file_path_index).unwrap();
+        arc.get_file_infos_mut()[from_index as usize].flags.set_is_redirect(true);
+        let target = arc.get_file_infos()[from_index as usize];
+        arc.get_file_in_folder_mut(&target, Region::None)
+            .file_info_index_and_load_type
+            .set_file_info_idx(target_index as u32);
+
+        assert!(matches!(
+            arc.resolve("fighter/mario/model/body/c01/model.numdlb", Region::None),
+            Err(LookupError::RedirectLoop { redirect, .. }) if redirect == hash40("fighter/mario/model/body/c01/model.numdlb")
+        ));
+    }
+
+    #[test]
+    fn resolve_dirs() {
+        let data = ArcBuilder::sample()
+            .file("fighter/mario/model/body/c01/model.numdlb", b"c01 model")
+            .symlink_dir("fighter/mario/model/body/c01", "fighter/mario/c00")
+            .build();
+        let arc = ArcFileRef::new(&data).unwrap();
+
+        let resolved = arc.resolve_dir("fighter/mario/model/body/c01").unwrap();
+        assert_eq!(resolved.symlinks, [hash40("fighter/mario/c00"), hash40("fighter/mario/model/body/c00")]);
+        assert_eq!(resolved.dir_info.path.hash40(), hash40("fighter/mario/model/body/c00"));
+        assert!(resolved.shared.is_none());
+
+        let resolved = arc.resolve_dir("fighter/luigi/c00").unwrap();
+        assert!(resolved.symlinks.is_empty());
+        assert_eq!(resolved.shared.map(|group| group.file_count), Some(2));
+
+        let mut arc = ArcBuilder::sample().open();
+        let c00 = arc.get_dir_infos().iter().position(|dir| dir.path.hash40() == hash40("fighter/mario/c00")).unwrap();
+        let group = arc.get_dir_infos()[c00].path.index() as usize;
+        arc.get_folder_offsets_mut()[group].directory_index = c00 as u32;
+        assert!(matches!(
+            arc.resolve_dir("fighter/mario/c00"),
+            Err(LookupError::RedirectLoop { .. })
+        ));
+    }
+}
@@ -0,0 +1,122 @@
+use std::collections::HashMap;
+
+use crate::region::REGION_COUNT;
+use crate::{ArcLookup, FileDataIdx, FilePathIdx, Hash40, Region};
+
+/// Every path which references each file data, for finding which files share their data without
+/// going through every file in the arc. Created by [`ArcFile::reverse_index`](crate::ArcFile::reverse_index).
+pub struct ReverseIndex {
+    /// Paths of non-regional files, which reference the same file data in every region
+    paths: HashMap<FileDataIdx, Vec<Hash40>>,
+    /// Paths of regional files, indexed by region
+    regional_paths: Vec<HashMap<FileDataIdx, Vec<Hash40>>>,
+}
+
+impl ReverseIndex {
+    /// Go through every file path in the arc, skipping any whose file data can't be found due to
+    /// a corrupt index
+    pub fn generate<A: ArcLookup + ?Sized>(arc: &A) -> Self {
+        let mut paths: HashMap<FileDataIdx, Vec<Hash40>> = HashMap::new();
+        let mut regional_paths: Vec<HashMap<FileDataIdx, Vec<Hash40>>> = vec![HashMap::new(); REGION_COUNT];
+
+        for (index, file_path) in arc.get_file_paths().iter().enumerate() {
+            let path = file_path.path.hash40();
+            let file_info = match arc.try_get_file_info_from_path_index(FilePathIdx(index as u32)) {
+                Ok(file_info) => file_info,
+                Err(_) => continue,
+            };
+
+            if file_info.flags.is_regional() {
+                for (region, region_paths) in regional_paths.iter_mut().enumerate() {
+                    if let Ok(file_in_folder) = arc.try_get_file_in_folder(file_info, Region::from(region)) {
+                        region_paths.entry(file_in_folder.file_data_index).or_default().push(path);
+                    }
+                }
+            } else if let Ok(file_in_folder) = arc.try_get_file_in_folder(file_info, Region::None) {
+                paths.entry(file_in_folder.file_data_index).or_default().push(path);
+            }
+        }
+
+        Self { paths, regional_paths }
+    }
+
+    /// The paths of every file which uses the file data in the given region. Non-regional files
+    /// come first, followed by regional ones, each in the order they appear in the file paths.
+    pub fn paths(&self, file_data_index: FileDataIdx, region: Region) -> Vec<Hash40> {
+        let paths = self.paths.get(&file_data_index).map(Vec::as_slice).unwrap_or_default();
+        let regional_paths = self.regional_paths[region as usize]
+            .get(&file_data_index)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+
+        [paths, regional_paths].concat()
+    }
+
+    /// Every file data used by more than one path in the given region, along with those paths,
+    /// ordered by file data index
+    pub fn shared(&self, region: Region) -> Vec<(FileDataIdx, Vec<Hash40>)> {
+        let mut file_datas: Vec<_> = self.paths.keys()
+            .chain(self.regional_paths[region as usize].keys())
+            .copied()
+            .collect();
+        file_datas.sort_unstable();
+        file_datas.dedup();
+
+        file_datas.into_iter()
+            .map(|file_data_index| (file_data_index, self.paths(file_data_index, region)))
+            .filter(|(_, paths)| paths.len() > 1)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_arc::ArcBuilder;
+    use crate::{hash40, ArcFileRef};
+
+    #[test]
+    fn shared_files_match_scan() {
+        let builder = ArcBuilder::sample();
+        let data = builder.build();
+        let scanned = ArcFileRef::new(&data).unwrap();
+        let indexed = builder.open();
+
+        for region in [Region::None, Region::UsEnglish, Region::Korean] {
+            for file_path in indexed.get_file_paths() {
+                let hash = file_path.path.hash40();
+                let mut expected = scanned.get_shared_files(hash, region).unwrap();
+                let mut shared = indexed.get_shared_files(hash, region).unwrap();
+                expected.sort();
+                shared.sort();
+                assert_eq!(shared, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn file_data_paths() {
+        let mut arc = ArcBuilder::sample().open();
+        let eye = hash40("fighter/mario/model/body/c00/leyes_eye_mario_l_col.nutexb");
+        let msbt = hash40("ui/message/msg_name.msbt");
+
+        let file_data_index = |arc: &crate::ArcFile, hash, region| {
+            arc.try_get_file_in_folder(arc.get_file_info_from_hash(hash).unwrap(), region).unwrap().file_data_index
+        };
+
+        let eye_data = file_data_index(&arc, eye, Region::None);
+        assert_eq!(arc.get_file_data_paths(eye_data, Region::Korean), [
+            hash40("fighter/luigi/model/body/c00/leyes_eye_mario_l_col.nutexb"),
+            eye,
+        ]);
+
+        let korean = file_data_index(&arc, msbt, Region::Korean);
+        assert_eq!(arc.get_file_data_paths(korean, Region::Korean), [msbt]);
+        assert!(arc.get_file_data_paths(korean, Region::Japanese).is_empty());
+
+        // Pointing the Japanese msbt at the Korean data is picked up by the next query
+        let file_info = *arc.get_file_info_from_hash(msbt).unwrap();
+        arc.get_file_in_folder_mut(&file_info, Region::Japanese).file_data_index = korean;
+        assert_eq!(arc.get_file_data_paths(korean, Region::Japanese), [msbt]);
+    }
+}
@@ -1,4 +1,5 @@
-use crate::{ArcFile, HashLabels, Hash40};
+use crate::progress::ProgressCounter;
+use crate::{ArcFile, HashLabels, Hash40, LookupError, ProgressHook};
 
 use rayon::prelude::*;
 use fuzzy_matcher::FuzzyMatcher;
@@ -13,7 +14,7 @@ impl HashLabels {
         let mut labels: Vec<(i64, Hash40)> = self.labels
             .par_iter()
             .filter_map(|(hash, label)| {
-                matcher.fuzzy_match(&label, search).map(|score| (score, *hash))
+                matcher.fuzzy_match(label, search).map(|score| (score, *hash))
             })
             .collect();
 
@@ -25,8 +26,19 @@ impl HashLabels {
 
 impl ArcFile {
     pub fn generate_search_cache(&self) -> SearchCache {
+        self.generate_search_cache_with_progress(&())
+            .expect("search cache generation can only be cancelled by a progress hook")
+    }
+
+    /// Equivalent to [`generate_search_cache`](ArcFile::generate_search_cache) while reporting
+    /// progress to `hook`. Returns [`LookupError::Cancelled`] if the hook cancels generation.
+    pub fn generate_search_cache_with_progress(&self, hook: &dyn ProgressHook) -> Result<SearchCache, LookupError> {
+        let progress = ProgressCounter::new(hook, self.file_system.file_paths.len() as u64, 0);
+
         let mut cache = HashMap::<Hash40, Vec<Hash40>>::new();
         for file_path in &self.file_system.file_paths {
+            progress.check_cancelled()?;
+
             cache.entry(file_path.file_name.hash40())
                 .or_default()
                 .push(file_path.path.hash40());
@@ -34,28 +46,37 @@ impl ArcFile {
             cache.entry(file_path.parent.hash40())
                 .or_default()
                 .push(file_path.path.hash40());
+
+            progress.file_done(file_path.path.hash40(), 0);
         }
             
-        SearchCache(cache)
+        Ok(SearchCache { paths: cache, labels: self.labels.clone() })
     }
 }
 
 #[repr(C)]
-pub struct SearchCache(HashMap<Hash40, Vec<Hash40>>);
+pub struct SearchCache {
+    paths: HashMap<Hash40, Vec<Hash40>>,
+    labels: HashLabels,
+}
 
 impl SearchCache {
+    /// The labels of the arc the cache was generated from
+    pub fn labels(&self) -> &HashLabels {
+        &self.labels
+    }
+
     pub fn search(&self, term: &str, labels: &HashLabels, max: usize) -> Vec<Hash40> {
         let matches = labels.get_ordered_matches(term);
 
         matches.into_iter()
-            .map(|search_match| {
-                self.0
+            .flat_map(|search_match| {
+                self.paths
                     .get(&search_match)
                     .map(|x| &x[..])
                     .unwrap_or_else(|| &[][..])
                     .iter()
             })
-            .flatten()
             .take(max)
             .copied()
             .collect()
@@ -66,20 +87,22 @@ impl SearchCache {
 mod tests {
     use super::*;
 
+    use crate::test_arc::ArcBuilder;
+
     #[test]
     fn search() {
-        let labels = HashLabels::from_file("hash_labels.txt").unwrap();
-        let arc = ArcFile::open("data.arc").unwrap();
-
+        let builder = ArcBuilder::sample();
+        let mut labels = builder.labels();
+        labels.add_label("a00wait1.nuanmb");
+        let arc = builder.open();
 
-        let gen_cache = std::time::Instant::now();
         let search_cache = arc.generate_search_cache();
-        dbg!(gen_cache.elapsed());
+        let found = search_cache.search("a00wait", &labels, 20);
 
-        let search = std::time::Instant::now();
-        let found = search_cache.search("mari", &labels, 20);
-        dbg!(search.elapsed());
+        assert_eq!(found, [Hash40::from("fighter/mario/motion/body/c00/a00wait1.nuanmb")]);
 
-        dbg!(found);
+        // The cache keeps the labels of the arc it was generated from
+        let found = search_cache.search("fighter/mario/model/body/c00", search_cache.labels(), 20);
+        assert!(found.contains(&Hash40::from("fighter/mario/model/body/c00/model.numdlb")));
     }
 }
@@ -0,0 +1,774 @@
+//! A builder for small but structurally valid data.arc files, so that the lookups can be tested
+//! without a copy of the game.
+
+use std::collections::BTreeSet;
+
+use crate::region::REGION_COUNT;
+use crate::{hash40, ArcFile, Hash40, HashLabels, Region};
+
+const MAGIC: u64 = 0xABCD_EF98_7654_3210;
+const NO_INDEX: u32 = 0xFF_FFFF;
+const BUCKET_COUNT: usize = 4;
+
+const DIR_REDIRECTED: u32 = 1 << 26;
+const DIR_SYMLINK: u32 = 1 << 28;
+
+const FILE_REDIRECT: u32 = 1 << 4;
+const FILE_REGIONAL: u32 = 1 << 15;
+
+const DATA_COMPRESSED: u32 = 0b01;
+const DATA_ZSTD: u32 = 0b10;
+
+enum FileKind {
+    Plain { data: Vec<u8>, compressed: bool },
+    Regional(Vec<Vec<u8>>),
+    /// An index into the shared datas
+    Shared(usize),
+    /// The path of the file whose data is used instead
+    Redirect(String),
+}
+
+struct FileEntry {
+    path: String,
+    kind: FileKind,
+}
+
+/// A stream file's path and data
+type Stream = (String, Vec<u8>);
+
+enum Redirect {
+    Symlink(String),
+    Shared(String),
+}
+
+/// Builds an in-memory data.arc with the given files, directories and streams.
+///
+/// Every directory containing a file gets a `DirInfo` and a mass-loading group of its own, with
+/// shared files stored in a separate group at the start of the shared section. The file system
+/// table is compressed unless [`uncompressed_table`](ArcBuilder::uncompressed_table) is used.
+pub(crate) struct ArcBuilder {
+    files: Vec<FileEntry>,
+    shared: Vec<Vec<u8>>,
+    streams: Vec<Stream>,
+    redirects: Vec<(String, Redirect)>,
+    compress_table: bool,
+    table_patches: Vec<(usize, Vec<u8>)>,
+}
+
+impl ArcBuilder {
+    pub(crate) fn new() -> Self {
+        Self {
+            files: Vec::new(),
+            shared: Vec::new(),
+            streams: Vec::new(),
+            redirects: Vec::new(),
+            compress_table: true,
+            table_patches: Vec::new(),
+        }
+    }
+
+    /// The miniature arc used by most tests
+    pub(crate) fn sample() -> Self {
+        Self::new()
+            .file("fighter/mario/model/body/c00/model.numdlb", b"mario model")
+            .compressed_file("fighter/mario/model/body/c00/def_mario_001_col.nutexb", &[0x55; 0x300])
+            .file("fighter/mario/motion/body/c00/a00wait1.nuanmb", b"wait")
+            .file("fighter/luigi/model/body/c00/model.numdlb", b"luigi model")
+            .shared_file(
+                &[
+                    "fighter/mario/model/body/c00/leyes_eye_mario_l_col.nutexb",
+                    "fighter/luigi/model/body/c00/leyes_eye_mario_l_col.nutexb",
+                ],
+                b"shared eyes",
+            )
+            .regional_file("ui/message/msg_name.msbt", |region| format!("name {:?}", region).into_bytes())
+            .symlink_dir("fighter/mario/c00", "fighter/mario/model/body/c00")
+            .shared_dir("fighter/luigi/c00", "fighter/luigi/model/body/c00")
+            .stream_file("stream:/sound/bgm/bgm_crs2_01_menu.nus3audio", b"menu music")
+            .stream_file("stream:/sound/bgm/bgm_crs2_01_menu.nus3bank", b"menu bank")
+            .stream_file("stream:/movie/opening.webm", b"opening movie")
+    }
+
+    pub(crate) fn file(mut self, path: &str, data: &[u8]) -> Self {
+        self.push_file(path, FileKind::Plain { data: data.to_vec(), compressed: false });
+        self
+    }
+
+    /// Add a file stored as a zstd frame
+    pub(crate) fn compressed_file(mut self, path: &str, data: &[u8]) -> Self {
+        self.push_file(path, FileKind::Plain { data: data.to_vec(), compressed: true });
+        self
+    }
+
+    /// Add a single file data to the shared section, used by every one of `paths`
+    pub(crate) fn shared_file(mut self, paths: &[&str], data: &[u8]) -> Self {
+        let index = self.shared.len();
+        self.shared.push(data.to_vec());
+
+        for path in paths {
+            self.push_file(path, FileKind::Shared(index));
+        }
+
+        self
+    }
+
+    /// Add a file with separate contents for every region
+    pub(crate) fn regional_file<F: Fn(Region) -> Vec<u8>>(mut self, path: &str, data: F) -> Self {
+        let datas = (0..REGION_COUNT).map(|region| data(Region::from(region))).collect();
+        self.push_file(path, FileKind::Regional(datas));
+        self
+    }
+
+    /// Add a file which is redirected to the file info of `target`, using its data
+    pub(crate) fn redirect_file(mut self, path: &str, target: &str) -> Self {
+        self.push_file(path, FileKind::Redirect(target.to_owned()));
+        self
+    }
+
+    /// Add a stream file, which must be within a quick dir such as `stream:/sound/bgm`
+    pub(crate) fn stream_file(mut self, path: &str, data: &[u8]) -> Self {
+        self.streams.push((path.to_owned(), data.to_vec()));
+        self
+    }
+
+    /// Add a directory which is a symlink to the `DirInfo` of `target`
+    pub(crate) fn symlink_dir(mut self, path: &str, target: &str) -> Self {
+        self.redirects.push((path.to_owned(), Redirect::Symlink(target.to_owned())));
+        self
+    }
+
+    /// Add a directory which is redirected to a `DirectoryOffset` holding the files of `target`
+    pub(crate) fn shared_dir(mut self, path: &str, target: &str) -> Self {
+        self.redirects.push((path.to_owned(), Redirect::Shared(target.to_owned())));
+        self
+    }
+
+    pub(crate) fn uncompressed_table(mut self) -> Self {
+        self.compress_table = false;
+        self
+    }
+
+    /// Overwrite part of the decompressed file system table once it has been built, for producing
+    /// corrupt arcs
+    pub(crate) fn patch_table(mut self, offset: usize, bytes: &[u8]) -> Self {
+        self.table_patches.push((offset, bytes.to_vec()));
+        self
+    }
+
+    fn push_file(&mut self, path: &str, kind: FileKind) {
+        self.files.push(FileEntry { path: path.to_owned(), kind });
+    }
+
+    /// Labels for every file, directory and stream in the arc
+    pub(crate) fn labels(&self) -> HashLabels {
+        let mut labels = HashLabels::new();
+        for label in self.all_labels() {
+            labels.add_label(label);
+        }
+
+        labels
+    }
+
+    fn all_labels(&self) -> Vec<String> {
+        self.files.iter()
+            .map(|file| file.path.clone())
+            .chain(self.dirs())
+            .chain(self.streams.iter().map(|(path, _)| path.clone()))
+            .collect()
+    }
+
+    /// Every directory in the arc, including the parents of other directories
+    fn dirs(&self) -> BTreeSet<String> {
+        let mut dirs = BTreeSet::new();
+        let paths = self.files.iter()
+            .filter_map(|file| parent(&file.path))
+            .chain(self.redirects.iter().map(|(path, _)| path.as_str()));
+
+        for path in paths {
+            let mut path = Some(path);
+            while let Some(dir) = path {
+                dirs.insert(dir.to_owned());
+                path = parent(dir);
+            }
+        }
+
+        dirs
+    }
+
+    pub(crate) fn build(&self) -> Vec<u8> {
+        let (stream_section, stream_table) = self.build_streams();
+        let (file_section, shared_start, file_table) = self.build_files();
+
+        let stream_section_offset = 0x100;
+        let file_section_offset = align(stream_section_offset + stream_section.len(), 0x100);
+        let shared_section_offset = file_section_offset + shared_start;
+        let file_system_offset = align(file_section_offset + file_section.len(), 0x100);
+
+        let mut table = self.build_table(stream_table.finish(stream_section_offset as u64), file_table);
+        for (offset, bytes) in &self.table_patches {
+            table[*offset..*offset + bytes.len()].copy_from_slice(bytes);
+        }
+
+        let mut arc = Vec::new();
+        for value in &[
+            MAGIC,
+            stream_section_offset as u64,
+            file_section_offset as u64,
+            shared_section_offset as u64,
+            file_system_offset as u64,
+            0,
+        ] {
+            put_u64(&mut arc, *value);
+        }
+
+        arc.resize(stream_section_offset, 0);
+        arc.extend_from_slice(&stream_section);
+        arc.resize(file_section_offset, 0);
+        arc.extend_from_slice(&file_section);
+        arc.resize(file_system_offset, 0);
+
+        if self.compress_table {
+            let compressed = zstd_frame(&table);
+            put_u32(&mut arc, 0x10);
+            put_u32(&mut arc, table.len() as u32);
+            put_u32(&mut arc, compressed.len() as u32);
+            put_u32(&mut arc, compressed.len() as u32 + 0x10);
+            arc.extend_from_slice(&compressed);
+        } else {
+            arc.extend_from_slice(&table);
+        }
+
+        arc
+    }
+
+    /// Open the arc with the labels of every file and directory in it
+    pub(crate) fn open(&self) -> ArcFile {
+        ArcFile::from_reader_with_labels(std::io::Cursor::new(self.build()), self.labels()).unwrap()
+    }
+
+    fn build_streams(&self) -> (Vec<u8>, StreamTables) {
+        // Stream entries are grouped by their quick dir, in the order the quick dirs are first seen
+        let mut quick_dirs: Vec<(&str, Vec<&Stream>)> = Vec::new();
+        for stream in &self.streams {
+            let name = quick_dir_name(&stream.0);
+            match quick_dirs.iter_mut().find(|(dir, _)| *dir == name) {
+                Some((_, streams)) => streams.push(stream),
+                None => quick_dirs.push((name, vec![stream])),
+            }
+        }
+
+        let mut section = Vec::new();
+        let mut tables = StreamTables::default();
+        for (name, streams) in quick_dirs {
+            let start = tables.entries.len();
+            for (path, data) in streams {
+                pad_to(&mut section, 0x10);
+
+                let index = tables.datas.len() as u32;
+                tables.hashes.push((hash40(path), tables.entries.len() as u32));
+                tables.entries.push((hash40(path), index));
+                tables.file_indices.push(index);
+                tables.datas.push((data.len() as u64, section.len() as u64));
+
+                section.extend_from_slice(data);
+            }
+
+            tables.quick_dirs.push((hash40(name), (tables.entries.len() - start) as u32, start as u32));
+        }
+
+        tables.hashes.sort_by_key(|(hash, _)| *hash);
+
+        (section, tables)
+    }
+
+    fn build_files(&self) -> (Vec<u8>, usize, FileTables) {
+        let dirs: Vec<String> = self.dirs().into_iter().collect();
+        let dir_index = |path: &str| dirs.iter().position(|dir| dir == path).unwrap() as u32;
+        let shared_group = dirs.len() as u32;
+
+        let non_shared_datas: usize = self.files.iter()
+            .map(|file| match file.kind {
+                FileKind::Plain { .. } => 1,
+                FileKind::Regional(_) => REGION_COUNT,
+                FileKind::Shared(_) | FileKind::Redirect(_) => 0,
+            })
+            .sum();
+
+        let mut section = Vec::new();
+        let mut tables = FileTables::default();
+        let mut file_redirects = Vec::new();
+
+        for (index, dir) in dirs.iter().enumerate() {
+            pad_to(&mut section, 0x10);
+            let group_start = section.len();
+            let data_start = tables.datas.len();
+            let info_start = tables.infos.len();
+
+            for file in self.files.iter().filter(|file| parent(&file.path) == Some(dir)) {
+                let info_index = tables.infos.len() as u32;
+                let info_to_data_index = tables.info_to_datas.len() as u32;
+
+                let mut flags = 0;
+                match &file.kind {
+                    FileKind::Plain { data, compressed } => {
+                        let data_index = tables.datas.len() as u32;
+                        tables.datas.push(place_data(&mut section, group_start, data, *compressed));
+                        tables.info_to_datas.push((index as u32, data_index, info_index));
+                    }
+                    FileKind::Regional(datas) => {
+                        flags |= FILE_REGIONAL;
+                        for data in datas {
+                            let data_index = tables.datas.len() as u32;
+                            tables.datas.push(place_data(&mut section, group_start, data, false));
+                            tables.info_to_datas.push((index as u32, data_index, info_index));
+                        }
+                    }
+                    FileKind::Shared(shared_index) => {
+                        let data_index = (non_shared_datas + shared_index) as u32;
+                        tables.info_to_datas.push((shared_group, data_index, info_index));
+                    }
+                    FileKind::Redirect(target) => {
+                        flags |= FILE_REDIRECT;
+                        file_redirects.push((info_to_data_index as usize, target));
+                        tables.info_to_datas.push((0, 0, 0));
+                    }
+                }
+
+                tables.paths.push(PathEntry {
+                    path: hash40(&file.path),
+                    parent: hash40(dir),
+                    parent_index: index as u32,
+                    file_name: hash40(file_name(&file.path)),
+                    ext: hash40(extension(&file.path)),
+                });
+                tables.infos.push((info_index, info_index, info_to_data_index, flags));
+            }
+
+            let size = (section.len() - group_start) as u32;
+            tables.folder_offsets.push(FolderOffset {
+                offset: group_start as u64,
+                decomp_size: tables.datas[data_start..].iter().map(|data| data.decomp_size).sum(),
+                size,
+                file_start_index: data_start as u32,
+                file_count: (tables.datas.len() - data_start) as u32,
+                directory_index: NO_INDEX,
+            });
+
+            let children: Vec<&String> = dirs.iter().filter(|child| parent(child) == Some(dir)).collect();
+            tables.dirs.push(DirEntry {
+                path: hash40(dir),
+                name: hash40(file_name(dir)),
+                parent: hash40(parent(dir).unwrap_or("")),
+                file_info_start: info_start as u32,
+                file_count: (tables.infos.len() - info_start) as u32,
+                child_start: tables.children.len() as u32,
+                child_count: children.len() as u32,
+                flags: 0,
+            });
+
+            for child in children {
+                tables.children.push((hash40(child), dir_index(child)));
+            }
+        }
+
+        // Shared files are stored after a gap, as a file is only considered shared if it is
+        // strictly past the start of the shared section
+        pad_to(&mut section, 0x10);
+        let shared_start = section.len();
+        section.resize(shared_start + 0x10, 0);
+        let data_start = tables.datas.len();
+        for data in &self.shared {
+            tables.datas.push(place_data(&mut section, shared_start, data, false));
+        }
+
+        tables.folder_offsets.push(FolderOffset {
+            offset: shared_start as u64,
+            decomp_size: tables.datas[data_start..].iter().map(|data| data.decomp_size).sum(),
+            size: (section.len() - shared_start) as u32,
+            file_start_index: data_start as u32,
+            file_count: (tables.datas.len() - data_start) as u32,
+            directory_index: NO_INDEX,
+        });
+
+        // Redirected files point to the file info of their target, but use the data of whichever
+        // file the chain of redirections ends at
+        let info_index = |path: &str| tables.paths.iter().position(|entry| entry.path == hash40(path)).unwrap();
+        let resolved: Vec<_> = file_redirects.iter()
+            .map(|(_, target)| {
+                let mut end = target.as_str();
+                for _ in 0..file_redirects.len() {
+                    match self.files.iter().find(|file| file.path == end).map(|file| &file.kind) {
+                        Some(FileKind::Redirect(next)) => end = next,
+                        _ => break,
+                    }
+                }
+
+                let (folder_offset_index, data_index, _) = tables.info_to_datas[tables.infos[info_index(end)].2 as usize];
+                (folder_offset_index, data_index, info_index(target) as u32)
+            })
+            .collect();
+
+        for ((info_to_data_index, _), info_to_data) in file_redirects.iter().zip(resolved) {
+            tables.info_to_datas[*info_to_data_index] = info_to_data;
+        }
+
+        for (path, redirect) in &self.redirects {
+            let dir = dir_index(path) as usize;
+            tables.dirs[dir].flags |= DIR_REDIRECTED;
+
+            tables.folder_offsets[dir].directory_index = match redirect {
+                Redirect::Symlink(target) => {
+                    tables.dirs[dir].flags |= DIR_SYMLINK;
+                    dir_index(target)
+                }
+                Redirect::Shared(target) => {
+                    // The target of a shared redirection lists FileInfos rather than FileDatas
+                    let target_index = dir_index(target) as usize;
+                    let target = &tables.dirs[target_index];
+                    let group = FolderOffset {
+                        offset: tables.folder_offsets[target_index].offset,
+                        decomp_size: 0,
+                        size: 0,
+                        file_start_index: target.file_info_start,
+                        file_count: target.file_count,
+                        directory_index: NO_INDEX,
+                    };
+
+                    tables.folder_offsets.push(group);
+                    tables.folder_offsets.len() as u32 - 1
+                }
+            };
+        }
+
+        (section, shared_start, tables)
+    }
+
+    fn build_table(&self, streams: Vec<u8>, files: FileTables) -> Vec<u8> {
+        let mut table = vec![0; 0x100];
+
+        table.extend_from_slice(&streams);
+
+        // Hashes are split into buckets by their value, and each bucket is sorted
+        let mut buckets = vec![Vec::new(); BUCKET_COUNT];
+        for (index, path) in files.paths.iter().enumerate() {
+            buckets[(path.path.as_u64() % BUCKET_COUNT as u64) as usize].push((path.path, index as u32));
+        }
+
+        put_u32(&mut table, files.paths.len() as u32);
+        put_u32(&mut table, BUCKET_COUNT as u32);
+        let mut start = 0;
+        for bucket in &mut buckets {
+            bucket.sort();
+            put_u32(&mut table, start);
+            put_u32(&mut table, bucket.len() as u32);
+            start += bucket.len() as u32;
+        }
+        for (hash, index) in buckets.iter().flatten() {
+            put_hash_to_index(&mut table, *hash, *index);
+        }
+
+        for (index, path) in files.paths.iter().enumerate() {
+            put_hash_to_index(&mut table, path.path, index as u32);
+            put_hash_to_index(&mut table, path.ext, 0);
+            put_hash_to_index(&mut table, path.parent, path.parent_index);
+            put_hash_to_index(&mut table, path.file_name, 0);
+        }
+
+        for (index, path) in files.paths.iter().enumerate() {
+            put_u32(&mut table, path.parent_index);
+            put_u32(&mut table, index as u32);
+        }
+
+        let mut dir_hashes: Vec<(Hash40, u32)> = files.dirs.iter()
+            .enumerate()
+            .map(|(index, dir)| (dir.path, index as u32))
+            .collect();
+        dir_hashes.sort();
+        for (hash, index) in dir_hashes {
+            put_hash_to_index(&mut table, hash, index);
+        }
+
+        for (index, dir) in files.dirs.iter().enumerate() {
+            put_hash_to_index(&mut table, dir.path, index as u32);
+            put_u64(&mut table, dir.name.as_u64());
+            put_u64(&mut table, dir.parent.as_u64());
+            put_u32(&mut table, 0);
+            put_u32(&mut table, 0);
+            put_u32(&mut table, dir.file_info_start);
+            put_u32(&mut table, dir.file_count);
+            put_u32(&mut table, dir.child_start);
+            put_u32(&mut table, dir.child_count);
+            put_u32(&mut table, dir.flags);
+        }
+
+        for folder_offset in &files.folder_offsets {
+            put_u64(&mut table, folder_offset.offset);
+            put_u32(&mut table, folder_offset.decomp_size);
+            put_u32(&mut table, folder_offset.size);
+            put_u32(&mut table, folder_offset.file_start_index);
+            put_u32(&mut table, folder_offset.file_count);
+            put_u32(&mut table, folder_offset.directory_index);
+        }
+
+        for (hash, index) in &files.children {
+            put_hash_to_index(&mut table, *hash, *index);
+        }
+
+        for (path_index, info_index, info_to_data_index, flags) in &files.infos {
+            put_u32(&mut table, *path_index);
+            put_u32(&mut table, *info_index);
+            put_u32(&mut table, *info_to_data_index);
+            put_u32(&mut table, *flags);
+        }
+
+        for (folder_offset_index, data_index, info_index) in &files.info_to_datas {
+            put_u32(&mut table, *folder_offset_index);
+            put_u32(&mut table, *data_index);
+            put_u32(&mut table, *info_index);
+        }
+
+        for data in &files.datas {
+            put_u32(&mut table, data.offset_in_folder);
+            put_u32(&mut table, data.comp_size);
+            put_u32(&mut table, data.decomp_size);
+            put_u32(&mut table, data.flags);
+        }
+
+        let header = [
+            table.len() as u32,
+            files.paths.len() as u32,
+            files.paths.len() as u32,
+            files.dirs.len() as u32,
+            files.folder_offsets.len() as u32,
+            files.children.len() as u32,
+            files.infos.len() as u32,
+            files.info_to_datas.len() as u32,
+            files.datas.len() as u32,
+            0,
+            0,
+            0,
+            0x10,
+            0x10,
+        ];
+
+        let mut fs_header = Vec::new();
+        for value in &header {
+            put_u32(&mut fs_header, *value);
+        }
+        fs_header.extend_from_slice(&[REGION_COUNT as u8 - 1, REGION_COUNT as u8 - 1, 0, 0]);
+        fs_header.resize(88, 0);
+        table[..88].copy_from_slice(&fs_header);
+
+        table
+    }
+}
+
+#[derive(Default)]
+struct StreamTables {
+    quick_dirs: Vec<(Hash40, u32, u32)>,
+    hashes: Vec<(Hash40, u32)>,
+    entries: Vec<(Hash40, u32)>,
+    file_indices: Vec<u32>,
+    /// The size and the offset relative to the stream section
+    datas: Vec<(u64, u64)>,
+}
+
+impl StreamTables {
+    /// Serialize the stream header and tables, given where the stream section will be placed
+    fn finish(self, stream_section_offset: u64) -> Vec<u8> {
+        let mut table = Vec::new();
+        put_u32(&mut table, self.quick_dirs.len() as u32);
+        put_u32(&mut table, self.entries.len() as u32);
+        put_u32(&mut table, self.file_indices.len() as u32);
+        put_u32(&mut table, self.datas.len() as u32);
+
+        for (hash, count, index) in &self.quick_dirs {
+            put_u32(&mut table, hash.crc32());
+            put_u32(&mut table, hash.len() as u32 | (count << 8));
+            put_u32(&mut table, *index);
+        }
+
+        for (hash, index) in &self.hashes {
+            put_hash_to_index(&mut table, *hash, *index);
+        }
+
+        for (hash, index) in &self.entries {
+            put_hash_to_index(&mut table, *hash, *index);
+            put_u32(&mut table, 0);
+        }
+
+        for index in &self.file_indices {
+            put_u32(&mut table, *index);
+        }
+
+        for (size, offset) in &self.datas {
+            put_u64(&mut table, *size);
+            put_u64(&mut table, stream_section_offset + offset);
+        }
+
+        table
+    }
+}
+
+struct PathEntry {
+    path: Hash40,
+    parent: Hash40,
+    parent_index: u32,
+    file_name: Hash40,
+    ext: Hash40,
+}
+
+struct DirEntry {
+    path: Hash40,
+    name: Hash40,
+    parent: Hash40,
+    file_info_start: u32,
+    file_count: u32,
+    child_start: u32,
+    child_count: u32,
+    flags: u32,
+}
+
+#[derive(Clone, Copy)]
+struct FolderOffset {
+    offset: u64,
+    decomp_size: u32,
+    size: u32,
+    file_start_index: u32,
+    file_count: u32,
+    directory_index: u32,
+}
+
+struct DataEntry {
+    offset_in_folder: u32,
+    comp_size: u32,
+    decomp_size: u32,
+    flags: u32,
+}
+
+#[derive(Default)]
+struct FileTables {
+    paths: Vec<PathEntry>,
+    dirs: Vec<DirEntry>,
+    folder_offsets: Vec<FolderOffset>,
+    children: Vec<(Hash40, u32)>,
+    /// The path, info indice and info to data indices along with the flags of each file
+    infos: Vec<(u32, u32, u32, u32)>,
+    /// The folder offset, file data and file info indices of each info to data
+    info_to_datas: Vec<(u32, u32, u32)>,
+    datas: Vec<DataEntry>,
+}
+
+/// Append a file's data to the group starting at `group_start`
+fn place_data(section: &mut Vec<u8>, group_start: usize, data: &[u8], compressed: bool) -> DataEntry {
+    pad_to(section, 0x10);
+    let offset_in_folder = ((section.len() - group_start) >> 2) as u32;
+
+    let (stored, flags) = if compressed {
+        (zstd_frame(data), DATA_COMPRESSED | DATA_ZSTD)
+    } else {
+        (data.to_vec(), 0)
+    };
+    section.extend_from_slice(&stored);
+
+    DataEntry {
+        offset_in_folder,
+        comp_size: stored.len() as u32,
+        decomp_size: data.len() as u32,
+        flags,
+    }
+}
+
+/// Wrap data in a zstd frame made up of uncompressed blocks, which every zstd decoder can read
+/// without the need for a compressor
+pub(crate) fn zstd_frame(data: &[u8]) -> Vec<u8> {
+    const MAX_BLOCK_SIZE: usize = 0x2_0000;
+
+    let mut frame = Vec::new();
+    put_u32(&mut frame, 0xFD2F_B528);
+    // Single segment frame with an 8 byte content size
+    frame.push(0xE0);
+    put_u64(&mut frame, data.len() as u64);
+
+    let mut blocks = data.chunks(MAX_BLOCK_SIZE).peekable();
+    if blocks.peek().is_none() {
+        frame.extend_from_slice(&[1, 0, 0]);
+    }
+    while let Some(block) = blocks.next() {
+        let is_last = blocks.peek().is_none() as u32;
+        let header = is_last | ((block.len() as u32) << 3);
+        frame.extend_from_slice(&header.to_le_bytes()[..3]);
+        frame.extend_from_slice(block);
+    }
+
+    frame
+}
+
+fn quick_dir_name(path: &str) -> &str {
+    let dir = parent(path).unwrap_or(path);
+    dir.strip_prefix("stream:/sound/")
+        .or_else(|| dir.strip_prefix("stream:/"))
+        .unwrap_or(dir)
+}
+
+fn parent(path: &str) -> Option<&str> {
+    path.rfind('/').map(|pos| &path[..pos])
+}
+
+fn file_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap()
+}
+
+fn extension(path: &str) -> &str {
+    file_name(path).rsplit('.').next().unwrap()
+}
+
+fn align(value: usize, alignment: usize) -> usize {
+    value.div_ceil(alignment) * alignment
+}
+
+fn pad_to(data: &mut Vec<u8>, alignment: usize) {
+    data.resize(align(data.len(), alignment), 0);
+}
+
+fn put_u32(data: &mut Vec<u8>, value: u32) {
+    data.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u64(data: &mut Vec<u8>, value: u64) {
+    data.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_hash_to_index(data: &mut Vec<u8>, hash: Hash40, index: u32) {
+    put_u32(data, hash.crc32());
+    put_u32(data, hash.len() as u32 | (index << 8));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ArcLookup, ArcFileRef};
+
+    #[test]
+    fn sample_arc_is_valid() {
+        let arc = ArcBuilder::sample().open();
+        arc.validate().unwrap();
+
+        let data = ArcBuilder::sample().uncompressed_table().build();
+        ArcFileRef::new(&data).unwrap().validate().unwrap();
+    }
+
+    #[test]
+    fn zstd_frames_decode() {
+        let data: Vec<u8> = (0..0x3_0000_u32).map(|x| x as u8).collect();
+
+        let decode = |data: &[u8], limit| {
+            let mut out = Vec::new();
+            crate::zstd_backend::copy_decode_bounded(&zstd_frame(data)[..], &mut out, limit).map(|_| out)
+        };
+
+        assert_eq!(decode(&data, data.len() as u64).unwrap(), data);
+        assert!(decode(&[], 0).unwrap().is_empty());
+        assert!(decode(&data, data.len() as u64 - 1).is_err());
+    }
+}
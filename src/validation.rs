@@ -0,0 +1,312 @@
+use std::fmt;
+use std::ops::Range;
+
+use thiserror::Error;
+
+use crate::region::REGION_COUNT;
+use crate::ArcLookup;
+
+/// The tables of the filesystem, used for identifying which table an invalid index was found in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TableKind {
+    FileInfoBuckets,
+    FileHashToPathIndex,
+    FilePaths,
+    FileInfoIndices,
+    DirHashToInfoIndex,
+    DirInfos,
+    FolderOffsets,
+    FolderChildHashes,
+    FileInfos,
+    FileInfoToDatas,
+    FileDatas,
+    QuickDirs,
+    StreamHashToEntries,
+    StreamEntries,
+    StreamFileIndices,
+    StreamDatas,
+    FolderPathList,
+    PathListIndices,
+    PathList,
+}
+
+impl fmt::Display for TableKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            TableKind::FileInfoBuckets => "file info buckets",
+            TableKind::FileHashToPathIndex => "file hash to path index",
+            TableKind::FilePaths => "file paths",
+            TableKind::FileInfoIndices => "file info indices",
+            TableKind::DirHashToInfoIndex => "dir hash to info index",
+            TableKind::DirInfos => "dir infos",
+            TableKind::FolderOffsets => "folder offsets",
+            TableKind::FolderChildHashes => "folder child hashes",
+            TableKind::FileInfos => "file infos",
+            TableKind::FileInfoToDatas => "file info to datas",
+            TableKind::FileDatas => "file datas",
+            TableKind::QuickDirs => "quick dirs",
+            TableKind::StreamHashToEntries => "stream hash to entries",
+            TableKind::StreamEntries => "stream entries",
+            TableKind::StreamFileIndices => "stream file indices",
+            TableKind::StreamDatas => "stream datas",
+            TableKind::FolderPathList => "folder path list",
+            TableKind::PathListIndices => "path list indices",
+            TableKind::PathList => "path list",
+        };
+
+        f.write_str(name)
+    }
+}
+
+/// An index in one of the filesystem's tables which doesn't point to a valid entry
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    #[error("entry {index} of the {table} references entry {target_index} of the {target}, which only has {len} entries")]
+    IndexOutOfBounds {
+        table: TableKind,
+        index: usize,
+        target: TableKind,
+        target_index: usize,
+        len: usize,
+    },
+
+    #[error("entry {index} of the {table} references entries {start}..{end} of the {target}, which only has {len} entries")]
+    RangeOutOfBounds {
+        table: TableKind,
+        index: usize,
+        target: TableKind,
+        start: usize,
+        end: usize,
+        len: usize,
+    },
+
+    #[error("the {table} is empty, but the {required_by} is not")]
+    EmptyTable {
+        table: TableKind,
+        required_by: TableKind,
+    },
+}
+
+fn check_index(table: TableKind, index: usize, target: TableKind, target_index: usize, len: usize) -> Result<(), ValidationError> {
+    if target_index < len {
+        Ok(())
+    } else {
+        Err(ValidationError::IndexOutOfBounds { table, index, target, target_index, len })
+    }
+}
+
+fn check_range(table: TableKind, index: usize, target: TableKind, range: Range<usize>, len: usize) -> Result<(), ValidationError> {
+    if range.start <= range.end && range.end <= len {
+        Ok(())
+    } else {
+        Err(ValidationError::RangeOutOfBounds {
+            table,
+            index,
+            target,
+            start: range.start,
+            end: range.end,
+            len,
+        })
+    }
+}
+
+pub(crate) fn validate<A: ArcLookup + ?Sized>(arc: &A) -> Result<(), ValidationError> {
+    use TableKind::*;
+
+    let buckets = arc.get_file_info_buckets();
+    let file_hash_to_path_index = arc.get_file_hash_to_path_index();
+    let file_paths = arc.get_file_paths();
+    let file_info_indices = arc.get_file_info_indices();
+    let dir_hash_to_info_index = arc.get_dir_hash_to_info_index();
+    let dir_infos = arc.get_dir_infos();
+    let folder_offsets = arc.get_folder_offsets();
+    let folder_child_hashes = arc.get_folder_child_hashes();
+    let file_infos = arc.get_file_infos();
+    let file_info_to_datas = arc.get_file_info_to_datas();
+    let file_datas = arc.get_file_datas();
+
+    if buckets.is_empty() && !file_hash_to_path_index.is_empty() {
+        return Err(ValidationError::EmptyTable {
+            table: FileInfoBuckets,
+            required_by: FileHashToPathIndex,
+        })
+    }
+
+    for (i, bucket) in buckets.iter().enumerate() {
+        let start = bucket.start as usize;
+        let range = start..start.saturating_add(bucket.count as usize);
+        check_range(FileInfoBuckets, i, FileHashToPathIndex, range, file_hash_to_path_index.len())?;
+    }
+
+    for (i, hash_to_path) in file_hash_to_path_index.iter().enumerate() {
+        check_index(FileHashToPathIndex, i, FilePaths, hash_to_path.index() as usize, file_paths.len())?;
+    }
+
+    for (i, file_path) in file_paths.iter().enumerate() {
+        check_index(FilePaths, i, FileInfoIndices, file_path.path.index() as usize, file_info_indices.len())?;
+    }
+
+    for (i, file_info_index) in file_info_indices.iter().enumerate() {
+        check_index(FileInfoIndices, i, FileInfos, usize::from(file_info_index.file_info_index), file_infos.len())?;
+    }
+
+    for (i, file_info) in file_infos.iter().enumerate() {
+        check_index(FileInfos, i, FilePaths, usize::from(file_info.file_path_index), file_paths.len())?;
+        check_index(FileInfos, i, FileInfoIndices, usize::from(file_info.file_info_indice_index), file_info_indices.len())?;
+
+        // Regional files have an entry for every region following the first
+        let start = usize::from(file_info.info_to_data_index);
+        let count = if file_info.flags.is_regional() { REGION_COUNT } else { 1 };
+        check_range(FileInfos, i, FileInfoToDatas, start..start.saturating_add(count), file_info_to_datas.len())?;
+    }
+
+    for (i, info_to_data) in file_info_to_datas.iter().enumerate() {
+        check_index(FileInfoToDatas, i, FolderOffsets, info_to_data.folder_offset_index as usize, folder_offsets.len())?;
+        check_index(FileInfoToDatas, i, FileDatas, usize::from(info_to_data.file_data_index), file_datas.len())?;
+    }
+
+    for (i, hash_to_info) in dir_hash_to_info_index.iter().enumerate() {
+        check_index(DirHashToInfoIndex, i, DirInfos, hash_to_info.index() as usize, dir_infos.len())?;
+    }
+
+    for (i, dir_info) in dir_infos.iter().enumerate() {
+        check_range(DirInfos, i, FileInfos, dir_info.file_info_range(), file_infos.len())?;
+        check_range(DirInfos, i, FolderChildHashes, dir_info.children_range(), folder_child_hashes.len())?;
+        check_index(DirInfos, i, FolderOffsets, dir_info.path.index() as usize, folder_offsets.len())?;
+
+        if dir_info.flags.redirected() {
+            let folder_offset = &folder_offsets[dir_info.path.index() as usize];
+            let directory_index = folder_offset.directory_index;
+
+            if directory_index != 0xFFFFFF {
+                let folder_offset_index = dir_info.path.index() as usize;
+                if dir_info.flags.is_symlink() {
+                    check_index(FolderOffsets, folder_offset_index, DirInfos, directory_index as usize, dir_infos.len())?;
+                } else {
+                    check_index(FolderOffsets, folder_offset_index, FolderOffsets, directory_index as usize, folder_offsets.len())?;
+
+                    // Unlike a directory's own group, the shared group it redirects to holds a
+                    // range of file infos
+                    let shared = folder_offsets[directory_index as usize];
+                    check_range(FolderOffsets, directory_index as usize, FileInfos, shared.range(), file_infos.len())?;
+                }
+            }
+        }
+    }
+
+    for (i, child) in folder_child_hashes.iter().enumerate() {
+        check_index(FolderChildHashes, i, DirInfos, child.index() as usize, dir_infos.len())?;
+    }
+
+    let stream_entries = arc.get_stream_entries();
+    let stream_file_indices = arc.get_stream_file_indices();
+    let stream_datas = arc.get_stream_datas();
+
+    for (i, quick_dir) in arc.get_quick_dirs().iter().enumerate() {
+        let start = quick_dir.index() as usize;
+        let range = start..start.saturating_add(quick_dir.count() as usize);
+        check_range(QuickDirs, i, StreamEntries, range, stream_entries.len())?;
+    }
+
+    for (i, hash_to_entry) in arc.get_stream_hash_to_entries().iter().enumerate() {
+        check_index(StreamHashToEntries, i, StreamEntries, hash_to_entry.index() as usize, stream_entries.len())?;
+    }
+
+    for (i, stream_entry) in stream_entries.iter().enumerate() {
+        check_index(StreamEntries, i, StreamFileIndices, stream_entry.index() as usize, stream_file_indices.len())?;
+    }
+
+    for (i, &stream_data_index) in stream_file_indices.iter().enumerate() {
+        check_index(StreamFileIndices, i, StreamDatas, stream_data_index as usize, stream_datas.len())?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_arc::ArcBuilder;
+    use crate::{DirInfo, FileInfoIndiceIdx, InfoToDataIdx};
+
+    #[test]
+    fn invalid_indices() {
+        let mut arc = ArcBuilder::sample().open();
+        arc.validate().unwrap();
+
+        let last = arc.file_system.file_info_to_datas.len() - 14;
+        let regional = arc.file_system.file_infos.iter_mut().find(|info| info.flags.is_regional()).unwrap();
+        regional.info_to_data_index = InfoToDataIdx(last as u32);
+        assert!(matches!(
+            arc.validate(),
+            Err(ValidationError::RangeOutOfBounds { table: TableKind::FileInfos, target: TableKind::FileInfoToDatas, .. })
+        ));
+
+        let mut arc = ArcBuilder::sample().open();
+        let len = arc.file_system.file_info_indices.len();
+        arc.file_system.file_infos[2].file_info_indice_index = FileInfoIndiceIdx(len as u32);
+        assert_eq!(
+            arc.validate(),
+            Err(ValidationError::IndexOutOfBounds {
+                table: TableKind::FileInfos,
+                index: 2,
+                target: TableKind::FileInfoIndices,
+                target_index: len,
+                len,
+            })
+        );
+
+        let mut arc = ArcBuilder::sample().open();
+        let symlink = arc.file_system.dir_infos.iter().position(|dir: &DirInfo| dir.flags.is_symlink()).unwrap();
+        let group = arc.file_system.dir_infos[symlink].path.index() as usize;
+        arc.file_system.folder_offsets[group].directory_index = 0x1000;
+        assert_eq!(
+            arc.validate(),
+            Err(ValidationError::IndexOutOfBounds {
+                table: TableKind::FolderOffsets,
+                index: group,
+                target: TableKind::DirInfos,
+                target_index: 0x1000,
+                len: arc.file_system.dir_infos.len(),
+            })
+        );
+
+        let mut arc = ArcBuilder::sample().open();
+        let shared = arc.file_system.dir_infos.iter()
+            .position(|dir: &DirInfo| dir.flags.redirected() && !dir.flags.is_symlink())
+            .unwrap();
+        let group = arc.file_system.dir_infos[shared].path.index() as usize;
+        let target = arc.file_system.folder_offsets[group].directory_index as usize;
+        arc.file_system.folder_offsets[target].file_count = 0x1000;
+        let start = arc.file_system.folder_offsets[target].file_start_index as usize;
+        assert_eq!(
+            arc.validate(),
+            Err(ValidationError::RangeOutOfBounds {
+                table: TableKind::FolderOffsets,
+                index: target,
+                target: TableKind::FileInfos,
+                start,
+                end: start + 0x1000,
+                len: arc.file_system.file_infos.len(),
+            })
+        );
+    }
+
+    #[test]
+    fn checks() {
+        assert!(check_index(TableKind::FileInfos, 0, TableKind::FilePaths, 2, 3).is_ok());
+        assert_eq!(
+            check_index(TableKind::FileInfos, 0, TableKind::FilePaths, 3, 3),
+            Err(ValidationError::IndexOutOfBounds {
+                table: TableKind::FileInfos,
+                index: 0,
+                target: TableKind::FilePaths,
+                target_index: 3,
+                len: 3,
+            })
+        );
+
+        assert!(check_range(TableKind::FileInfos, 1, TableKind::FileInfoToDatas, 1..16, 16).is_ok());
+        assert!(check_range(TableKind::FileInfos, 1, TableKind::FileInfoToDatas, 2..17, 16).is_err());
+    }
+}
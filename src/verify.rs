@@ -0,0 +1,264 @@
+use std::collections::HashMap;
+use std::io::{Seek, SeekFrom};
+
+use crate::batch::{read_coalesced, section_bounds, ReadRequest};
+use crate::lookups::decode_raw;
+use crate::progress::ProgressCounter;
+use crate::region::REGION_COUNT;
+use crate::{ArcLookup, FileData, FileDataIdx, Hash40, LookupError, ProgressHook, Region, ReverseIndex};
+
+/// The result of [`ArcLookup::verify`], listing every entry which failed verification
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    /// The number of file datas which were decompressed and checked
+    pub files_checked: u64,
+    /// The number of stream datas which were checked
+    pub streams_checked: u64,
+    pub issues: Vec<VerifyIssue>,
+}
+
+impl VerifyReport {
+    /// Whether every entry passed verification
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+#[derive(Debug)]
+pub struct VerifyIssue {
+    pub entry: VerifyEntry,
+    pub kind: VerifyIssueKind,
+}
+
+/// The entry of the arc which failed verification
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerifyEntry {
+    /// A file data, along with a path which uses it (or `Hash40(0)` if none do)
+    File {
+        path: Hash40,
+        file_data_index: FileDataIdx,
+    },
+    /// An index into the stream datas
+    Stream(usize),
+}
+
+#[derive(Debug)]
+pub enum VerifyIssueKind {
+    /// The file data is stored relative to a folder offset which does not exist
+    InvalidIndex,
+    /// No file info to data references the file data, so where it is stored is unknown
+    Unreferenced,
+    /// The data lies outside of the section it belongs in
+    OutOfBounds {
+        offset: u64,
+        size: u64,
+        section_start: u64,
+        section_end: u64,
+    },
+    /// The data could not be decompressed, including when it decompresses to more than the file
+    /// data claims it should
+    Unreadable(LookupError),
+    /// The decompressed data is smaller than the file data claims it is
+    SizeMismatch { expected: u64, actual: u64 },
+}
+
+pub(crate) fn verify<A: ArcLookup + ?Sized>(arc: &A, hook: &dyn ProgressHook) -> Result<VerifyReport, LookupError> {
+    let mut report = VerifyReport::default();
+
+    let arc_len = arc.get_file_reader().seek(SeekFrom::End(0))?;
+    let file_section = section_bounds(arc, arc.get_file_section_offset(), arc_len);
+    let stream_section = section_bounds(arc, arc.get_stream_section_offset(), arc_len);
+
+    for (index, stream_data) in arc.get_stream_datas().iter().enumerate() {
+        report.streams_checked += 1;
+
+        if let Some(kind) = check_bounds(stream_data.offset, stream_data.size, stream_section) {
+            report.issues.push(VerifyIssue {
+                entry: VerifyEntry::Stream(index),
+                kind,
+            });
+        }
+    }
+
+    let requests = file_data_requests(arc, file_section, &mut report);
+
+    let bytes_total = requests.iter().map(|request| request.key.2.decomp_size as u64).sum();
+    let progress = ProgressCounter::new(hook, requests.len() as u64, bytes_total);
+
+    read_coalesced(arc, requests, |(path, file_data_index, file_data), raw| {
+        progress.check_cancelled()?;
+
+        let flags = file_data.flags;
+        let expected = file_data.decomp_size as u64;
+        let kind = match raw.and_then(|raw| decode_raw(raw, flags.compressed(), flags.use_zstd(), expected)) {
+            Ok(data) if data.len() as u64 == expected => None,
+            Ok(data) => Some(VerifyIssueKind::SizeMismatch {
+                expected,
+                actual: data.len() as u64,
+            }),
+            Err(err) => Some(VerifyIssueKind::Unreadable(err)),
+        };
+
+        if let Some(kind) = kind {
+            report.issues.push(VerifyIssue {
+                entry: VerifyEntry::File { path, file_data_index },
+                kind,
+            });
+        }
+
+        report.files_checked += 1;
+        progress.file_done(path, expected);
+
+        Ok(())
+    })?;
+
+    Ok(report)
+}
+
+/// Find where every file data is stored, reporting any which can't be read and returning the
+/// rest. File datas are named after a path which uses them, but are checked whether or not any
+/// path reaches them.
+fn file_data_requests<A: ArcLookup + ?Sized>(
+    arc: &A,
+    file_section: (u64, u64),
+    report: &mut VerifyReport,
+) -> Vec<ReadRequest<(Hash40, FileDataIdx, FileData)>> {
+    let folder_offsets = arc.get_folder_offsets();
+    let reverse_index = ReverseIndex::generate(arc);
+
+    // File datas are stored relative to a folder offset, which only the file info to datas know
+    let mut folders = HashMap::new();
+    for info_to_data in arc.get_file_info_to_datas() {
+        folders.entry(info_to_data.file_data_index).or_insert(info_to_data.folder_offset_index);
+    }
+
+    let mut requests = Vec::new();
+    for (index, file_data) in arc.get_file_datas().iter().enumerate() {
+        let file_data_index = FileDataIdx(index as u32);
+        let path = first_path(&reverse_index, file_data_index);
+        let entry = VerifyEntry::File { path, file_data_index };
+
+        let folder_offset = match folders.get(&file_data_index) {
+            Some(&folder_offset_index) => folder_offsets.get(folder_offset_index as usize),
+            None => {
+                report.issues.push(VerifyIssue {
+                    entry,
+                    kind: VerifyIssueKind::Unreferenced,
+                });
+                continue
+            }
+        };
+
+        let folder_offset = match folder_offset {
+            Some(folder_offset) => folder_offset.offset,
+            None => {
+                report.issues.push(VerifyIssue {
+                    entry,
+                    kind: VerifyIssueKind::InvalidIndex,
+                });
+                continue
+            }
+        };
+
+        let offset = arc.get_file_section_offset()
+            .saturating_add(folder_offset)
+            .saturating_add((file_data.offset_in_folder as u64) << 2);
+        let size = file_data.comp_size as u64;
+
+        match check_bounds(offset, size, file_section) {
+            Some(kind) => report.issues.push(VerifyIssue { entry, kind }),
+            None => requests.push(ReadRequest {
+                key: (path, file_data_index, *file_data),
+                offset,
+                size,
+            }),
+        }
+    }
+
+    requests
+}
+
+/// The first path which uses a file data in any region, or `Hash40(0)` if none do
+fn first_path(reverse_index: &ReverseIndex, file_data_index: FileDataIdx) -> Hash40 {
+    (0..REGION_COUNT)
+        .find_map(|region| reverse_index.paths(file_data_index, Region::from(region)).first().copied())
+        .unwrap_or(Hash40(0))
+}
+
+fn check_bounds(offset: u64, size: u64, (section_start, section_end): (u64, u64)) -> Option<VerifyIssueKind> {
+    let in_bounds = offset >= section_start
+        && matches!(offset.checked_add(size), Some(end) if end <= section_end);
+
+    if in_bounds {
+        None
+    } else {
+        Some(VerifyIssueKind::OutOfBounds {
+            offset,
+            size,
+            section_start,
+            section_end,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_arc::ArcBuilder;
+
+    #[test]
+    fn verify_arc() {
+        let mut arc = ArcBuilder::sample().open();
+
+        let report = arc.verify().unwrap();
+        assert!(report.is_ok(), "{:?}", report.issues);
+        assert_eq!(report.files_checked, 20);
+        assert_eq!(report.streams_checked, 3);
+
+        let path = Hash40::from("fighter/mario/model/body/c00/model.numdlb");
+        let file_info = *arc.get_file_info_from_hash(path).unwrap();
+        arc.get_file_data_mut(&file_info, Region::None).decomp_size += 1;
+        arc.file_system.stream_datas[2].size = 0x10_0000;
+
+        let report = arc.verify().unwrap();
+        assert_eq!(report.issues.len(), 2);
+        assert!(matches!(report.issues[0], VerifyIssue {
+            entry: VerifyEntry::Stream(2),
+            kind: VerifyIssueKind::OutOfBounds { size: 0x10_0000, .. },
+        }));
+        assert!(matches!(report.issues[1], VerifyIssue {
+            entry: VerifyEntry::File { path: p, .. },
+            kind: VerifyIssueKind::SizeMismatch { expected: 12, actual: 11 },
+        } if p == path));
+
+        // File datas which no file info reaches are still checked
+        let mut arc = ArcBuilder::sample().open();
+        let regional = arc.file_system.file_infos.iter_mut().find(|info| info.flags.is_regional()).unwrap();
+        regional.flags.set_is_regional(false);
+        let info_to_data_index = usize::from(regional.info_to_data_index) + 3;
+        let file_data_index = arc.file_system.file_info_to_datas[info_to_data_index].file_data_index;
+        arc.file_system.file_datas[usize::from(file_data_index)].decomp_size += 1;
+        let extra = arc.file_system.file_datas[0];
+        arc.file_system.file_datas.push(extra);
+
+        let report = arc.verify().unwrap();
+        assert_eq!(report.files_checked, 20);
+        assert_eq!(report.issues.len(), 2);
+        assert!(matches!(report.issues[0], VerifyIssue {
+            entry: VerifyEntry::File { path: Hash40(0), file_data_index: FileDataIdx(20) },
+            kind: VerifyIssueKind::Unreferenced,
+        }));
+        assert!(matches!(report.issues[1], VerifyIssue {
+            entry: VerifyEntry::File { path: Hash40(0), file_data_index: i },
+            kind: VerifyIssueKind::SizeMismatch { .. },
+        } if i == file_data_index));
+    }
+
+    #[test]
+    fn bounds() {
+        assert!(check_bounds(0x10, 0x10, (0x10, 0x20)).is_none());
+        assert!(check_bounds(0x10, 0x11, (0x10, 0x20)).is_some());
+        assert!(check_bounds(0x8, 0x4, (0x10, 0x20)).is_some());
+        assert!(check_bounds(u64::MAX, 2, (0x10, u64::MAX)).is_some());
+    }
+}
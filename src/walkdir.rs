@@ -0,0 +1,215 @@
+use std::collections::HashSet;
+use std::slice;
+
+use crate::lookups::{checked, checked_range, top_level_dirs};
+use crate::{ArcLookup, DirInfo, DirectoryOffset, FileInfo, Hash40, LookupError, RedirectionRef, TableKind};
+
+/// The container a file was found in while walking the arc
+#[derive(Debug, Clone, Copy)]
+pub enum WalkdirDirectoryType<'a> {
+    /// A shared file group which a directory redirects to
+    FileGroup(&'a DirectoryOffset),
+    Directory(&'a DirInfo),
+}
+
+/// An iterator over every file under a directory, or the whole arc, created by
+/// [`ArcLookup::walk`] or [`ArcLookup::walk_dir`].
+///
+/// Each directory's files are yielded before those of its redirection (if followed) and then
+/// those of its children. Each directory is walked at most once, so symlinks which loop are
+/// harmless. Corrupt indices are yielded as errors, after which the walk continues with the next
+/// directory.
+pub struct WalkdirIter<'a, A: ?Sized> {
+    arc: &'a A,
+    follow_redirects: bool,
+    /// The containers left to walk, in reverse order
+    stack: Vec<WalkdirDirectoryType<'a>>,
+    /// The container currently being walked, along with its remaining files
+    current: Option<(WalkdirDirectoryType<'a>, slice::Iter<'a, FileInfo>)>,
+    visited: HashSet<Hash40>,
+}
+
+impl<'a, A: ArcLookup + ?Sized> WalkdirIter<'a, A> {
+    /// Walk every directory in the arc, starting from the top-level directories
+    pub fn new(arc: &'a A) -> Self {
+        let mut stack: Vec<_> = top_level_dirs(arc).map(WalkdirDirectoryType::Directory).collect();
+        stack.reverse();
+
+        Self::with_stack(arc, stack)
+    }
+
+    pub fn from_dir_info(arc: &'a A, dir_info: &'a DirInfo) -> Self {
+        Self::with_stack(arc, vec![WalkdirDirectoryType::Directory(dir_info)])
+    }
+
+    pub fn from_hash<Hash: Into<Hash40>>(arc: &'a A, hash: Hash) -> Result<Self, LookupError> {
+        Ok(Self::from_dir_info(arc, arc.get_dir_info_from_hash(hash)?))
+    }
+
+    fn with_stack(arc: &'a A, stack: Vec<WalkdirDirectoryType<'a>>) -> Self {
+        Self {
+            arc,
+            follow_redirects: false,
+            stack,
+            current: None,
+            visited: HashSet::new(),
+        }
+    }
+
+    /// Also walk the directory or shared file group that symlinked and shared directories
+    /// redirect to. Disabled by default.
+    pub fn follow_redirects(mut self, follow: bool) -> Self {
+        self.follow_redirects = follow;
+        self
+    }
+
+    /// Queue up the redirection and children of a directory, returning its files
+    fn enter_dir(&mut self, dir_info: &'a DirInfo) -> Result<&'a [FileInfo], LookupError> {
+        let dir_infos = self.arc.get_dir_infos();
+        let children = checked_range(self.arc.get_folder_child_hashes(), dir_info.children_range(), TableKind::FolderChildHashes)?;
+
+        for child in children.iter().rev() {
+            let child = checked(dir_infos, child.index() as usize, TableKind::DirInfos)?;
+            self.stack.push(WalkdirDirectoryType::Directory(child));
+        }
+
+        if self.follow_redirects {
+            match self.arc.try_get_directory_dependency_ref(dir_info)? {
+                Some(RedirectionRef::Symlink(dir_info)) => self.stack.push(WalkdirDirectoryType::Directory(dir_info)),
+                Some(RedirectionRef::Shared(group)) => self.stack.push(WalkdirDirectoryType::FileGroup(group)),
+                None => {}
+            }
+        }
+
+        checked_range(self.arc.get_file_infos(), dir_info.file_info_range(), TableKind::FileInfos)
+    }
+}
+
+impl<'a, A: ArcLookup + ?Sized> Iterator for WalkdirIter<'a, A> {
+    type Item = Result<WalkdirEntry<'a, A>, LookupError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((parent, files)) = &mut self.current {
+                if let Some(file_info) = files.next() {
+                    return Some(Ok(WalkdirEntry {
+                        arc: self.arc,
+                        parent: *parent,
+                        file_info,
+                    }))
+                }
+
+                self.current = None;
+            }
+
+            let parent = self.stack.pop()?;
+            let files = match parent {
+                WalkdirDirectoryType::Directory(dir_info) => {
+                    if !self.visited.insert(dir_info.path.hash40()) {
+                        continue
+                    }
+
+                    self.enter_dir(dir_info)
+                }
+                WalkdirDirectoryType::FileGroup(group) => {
+                    checked_range(self.arc.get_file_infos(), group.range(), TableKind::FileInfos)
+                }
+            };
+
+            match files {
+                Ok(files) => self.current = Some((parent, files.iter())),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+/// A file found by a [`WalkdirIter`]
+pub struct WalkdirEntry<'a, A: ?Sized> {
+    arc: &'a A,
+    parent: WalkdirDirectoryType<'a>,
+    file_info: &'a FileInfo,
+}
+
+impl<'a, A: ArcLookup + ?Sized> WalkdirEntry<'a, A> {
+    /// The directory or shared file group the file belongs to
+    pub fn parent(&self) -> WalkdirDirectoryType<'a> {
+        self.parent
+    }
+
+    pub fn file_info(&self) -> &'a FileInfo {
+        self.file_info
+    }
+
+    /// The hash of the file's full path
+    pub fn path(&self) -> Result<Hash40, LookupError> {
+        let file_path = checked(self.arc.get_file_paths(), usize::from(self.file_info.file_path_index), TableKind::FilePaths)?;
+
+        Ok(file_path.path.hash40())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_arc::ArcBuilder;
+    use crate::{hash40, ArcFileRef};
+
+    fn walk_paths<A: ArcLookup + ?Sized>(walk: WalkdirIter<A>) -> Vec<Hash40> {
+        walk.map(|entry| entry.unwrap().path().unwrap()).collect()
+    }
+
+    #[test]
+    fn walk() {
+        let data = ArcBuilder::sample().build();
+        let arc = ArcFileRef::new(&data).unwrap();
+
+        let mut paths = walk_paths(arc.walk());
+        paths.sort();
+        let mut expected: Vec<_> = arc.get_file_paths().iter().map(|path| path.path.hash40()).collect();
+        expected.sort();
+        assert_eq!(paths, expected);
+
+        assert_eq!(walk_paths(arc.walk_dir("fighter/mario/motion").unwrap()), [
+            hash40("fighter/mario/motion/body/c00/a00wait1.nuanmb"),
+        ]);
+    }
+
+    #[test]
+    fn follow_redirects() {
+        let data = ArcBuilder::sample().build();
+        let arc = ArcFileRef::new(&data).unwrap();
+
+        assert!(walk_paths(arc.walk_dir("fighter/mario/c00").unwrap()).is_empty());
+
+        let entries: Vec<_> = arc.walk_dir("fighter/mario/c00")
+            .unwrap()
+            .follow_redirects(true)
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(entries.len(), 3);
+        assert!(entries.iter().all(|entry| matches!(
+            entry.parent(),
+            WalkdirDirectoryType::Directory(dir) if dir.path.hash40() == hash40("fighter/mario/model/body/c00")
+        )));
+
+        let entries: Vec<_> = arc.walk_dir("fighter/luigi/c00")
+            .unwrap()
+            .follow_redirects(true)
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(entries.len(), 2);
+        assert!(entries.iter().all(|entry| matches!(entry.parent(), WalkdirDirectoryType::FileGroup(_))));
+    }
+
+    #[test]
+    fn corrupt_children() {
+        let mut arc = ArcBuilder::sample().open();
+        let fighter = arc.get_dir_info_from_hash("fighter").unwrap().children_range();
+        arc.file_system.folder_child_hashes[fighter.start].set_index(0xFF_FFFF);
+
+        let results: Vec<_> = arc.walk().collect();
+        assert!(matches!(results[0], Err(LookupError::CorruptIndex { table: TableKind::DirInfos, .. })));
+        assert!(results[1..].iter().all(Result::is_ok));
+    }
+}
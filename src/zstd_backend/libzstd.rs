@@ -1,2 +1,7 @@
-pub use zstd::stream::copy_decode;
-pub use zstd::decode_all;
+use std::io::{BufReader, Read, Result};
+
+pub type Decoder<R> = zstd::stream::read::Decoder<BufReader<R>>;
+
+pub fn decoder<R: Read>(source: R) -> Result<Decoder<R>> {
+    Decoder::new(source)
+}
@@ -16,19 +16,41 @@ compile_error!("Only one ZSTD backend can be enabled at a time");
 #[cfg(not(any(feature = "libzstd", feature = "rust-zstd", feature = "nozstd", doc)))]
 compile_error!("At least one ZSTD backend feature must be enabled");
 
+use std::io::{self, Read, Write};
+
+/// Decode a zstd frame into `destination`, failing once more than `limit` bytes have been
+/// decompressed rather than continuing to write out a decompression bomb
+pub fn copy_decode_bounded<R, W>(source: R, mut destination: W, limit: u64) -> io::Result<u64>
+    where R: Read,
+          W: Write,
+{
+    let mut decoder = decoder(source)?.take(limit.saturating_add(1));
+    let len = io::copy(&mut decoder, &mut destination)?;
+
+    if len > limit {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("zstd frame decompresses to more than the expected {:#x} bytes", limit),
+        ))
+    } else {
+        Ok(len)
+    }
+}
+
 #[cfg(not(any(feature = "libzstd", feature = "rust-zstd")))]
 mod template {
-    use std::io::{Read, Write, Result};
+    use std::io::{Read, Result};
 
-    pub fn copy_decode<R, W>(mut _source: R, mut _destination: W) -> Result<()>
-        where R: Read,
-              W: Write,
-    {
+    pub struct Decoder<R: Read>(R);
+
+    pub fn decoder<R: Read>(_source: R) -> Result<Decoder<R>> {
         todo!()
     }
 
-    pub fn decode_all<R: Read>(mut _source: R) -> Result<Vec<u8>> {
-        todo!()
+    impl<R: Read> Read for Decoder<R> {
+        fn read(&mut self, _buf: &mut [u8]) -> Result<usize> {
+            todo!()
+        }
     }
 }
 
@@ -1,22 +1,34 @@
-use std::io::{self, Error, ErrorKind, Read, Write, Result};
-use ruzstd::streaming_decoder::StreamingDecoder;
+use std::io::{Error, Read, Result};
+use ruzstd::frame_decoder::{BlockDecodingStrategy, FrameDecoder};
 
-pub fn copy_decode<R, W>(mut source: R, mut destination: W) -> Result<()>
-    where R: Read,
-          W: Write,
-{
-    let mut decoder = StreamingDecoder::new(&mut source)
-        .map_err(|err| Error::new(ErrorKind::Other, err))?;
+/// An owning equivalent of [`StreamingDecoder`](ruzstd::streaming_decoder::StreamingDecoder),
+/// which can only borrow its source
+pub struct Decoder<R: Read> {
+    decoder: FrameDecoder,
+    source: R,
+}
+
+pub fn decoder<R: Read>(mut source: R) -> Result<Decoder<R>> {
+    let mut decoder = FrameDecoder::new();
+    decoder.init(&mut source)
+        .map_err(Error::other)?;
 
-    io::copy(&mut decoder, &mut destination).map(|_| ())
+    Ok(Decoder { decoder, source })
 }
 
-pub fn decode_all<R: Read>(mut source: R) -> Result<Vec<u8>> {
-    let mut decoder = StreamingDecoder::new(&mut source)
-        .map_err(|err| Error::new(ErrorKind::Other, err))?;
+impl<R: Read> Read for Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if self.decoder.is_finished() && self.decoder.can_collect() == 0 {
+            return Ok(0);
+        }
 
-    let mut out = Vec::new();
-    decoder.read_to_end(&mut out)?;
+        while self.decoder.can_collect() < buf.len() && !self.decoder.is_finished() {
+            let additional_bytes_needed = buf.len() - self.decoder.can_collect();
+            self.decoder
+                .decode_blocks(&mut self.source, BlockDecodingStrategy::UptoBytes(additional_bytes_needed))
+                .map_err(|err| Error::other(format!("Error in the zstd decoder: {:?}", err)))?;
+        }
 
-    Ok(out)
+        self.decoder.read(buf)
+    }
 }